@@ -1,6 +1,10 @@
+pub mod chunked;
+pub mod error;
+pub mod headers;
 pub mod method;
 pub mod middleware;
 pub mod request;
 pub mod response;
 pub mod route;
 pub mod router;
+pub mod sse;
@@ -0,0 +1,95 @@
+use std::io::{self, Write};
+use std::net::TcpStream;
+
+use super::headers::Headers;
+use crate::server::shutdown::ShutdownHandle;
+
+// Size of the buffer written content is collected in before it is sent as a chunk.
+const CHUNK_BUFFER_SIZE: usize = 8 * 1024;
+
+// Function writing the content of a chunked response while it is sent, see Response::set_chunked.
+pub type ChunkedFunc = Box<dyn FnOnce(&mut ChunkedWriter) -> io::Result<()> + Send + 'static>;
+
+// Writer for the content of a chunked response (Transfer-Encoding: chunked).
+// Written content is buffered and sent to the client as one chunk when the buffer is full or flush is called, so call flush to send partial content right away.
+pub struct ChunkedWriter<'a> {
+    stream: &'a TcpStream,
+    chunked: bool,
+    buffer: Vec<u8>,
+    trailers: Vec<(String, String)>,
+    shutdown: Option<ShutdownHandle>,
+}
+
+impl<'a> ChunkedWriter<'a> {
+    // Create a writer for the stream. Without chunked encoding, the content is written as is (for HTTP/1.0 clients).
+    pub(crate) fn new(
+        stream: &'a TcpStream,
+        chunked: bool,
+        shutdown: Option<ShutdownHandle>,
+    ) -> Self {
+        Self {
+            stream,
+            chunked,
+            buffer: Vec::with_capacity(CHUNK_BUFFER_SIZE),
+            trailers: Vec::new(),
+            shutdown,
+        }
+    }
+
+    // Check if the server is shutting down, in which case long running content (e.g. an event stream) should be ended so the server can stop.
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown
+            .as_ref()
+            .is_some_and(|shutdown| shutdown.is_shutdown())
+    }
+
+    // Add a header sent after the content, e.g. a checksum computed while writing. Trailers are dropped for clients that do not support chunked responses.
+    // Invalid headers are logged and ignored, as for the headers of a response.
+    pub fn trailer(&mut self, name: &str, value: &str) {
+        if !Headers::is_valid(name, value) {
+            return;
+        }
+        self.trailers.push((name.to_string(), value.to_string()));
+    }
+
+    // End the content by sending the last (empty) chunk and the trailers.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.flush()?;
+        if !self.chunked {
+            return Ok(());
+        }
+        let mut end = String::from("0\r\n");
+        for (name, value) in &self.trailers {
+            end.push_str(&format!("{name}: {value}\r\n"));
+        }
+        end.push_str("\r\n");
+        let mut stream = self.stream;
+        stream.write_all(end.as_bytes())
+    }
+}
+
+impl Write for ChunkedWriter<'_> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buffer);
+        if self.buffer.len() >= CHUNK_BUFFER_SIZE {
+            self.flush()?;
+        }
+        Ok(buffer.len())
+    }
+
+    // Send the buffered content as one chunk.
+    fn flush(&mut self) -> io::Result<()> {
+        // An empty chunk would end the content.
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let mut stream = self.stream;
+        if self.chunked {
+            stream.write_all(format!("{:x}\r\n", self.buffer.len()).as_bytes())?;
+            self.buffer.extend_from_slice(b"\r\n");
+        }
+        stream.write_all(&self.buffer)?;
+        self.buffer.clear();
+        stream.flush()
+    }
+}
@@ -0,0 +1,96 @@
+use std::any::Any;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, ErrorKind};
+use std::num::{ParseFloatError, ParseIntError};
+use std::str::{ParseBoolError, Utf8Error};
+use std::string::FromUtf8Error;
+
+use crate::utils::general::get_status_message;
+
+// Error returned from a route function, turned into a response by the error handler of the server.
+// Any error type converts into a HttpError, so "?" can be used in route functions. I/O errors map to a matching status code, parse errors to 400 Bad Request and other errors to 500 Internal Server Error.
+#[derive(Debug, Clone)]
+pub struct HttpError {
+    pub status_code: usize,
+    pub message: String,
+}
+
+impl HttpError {
+    pub fn new(status_code: usize, message: &str) -> Self {
+        Self {
+            status_code,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn bad_request(message: &str) -> Self {
+        Self::new(400, message)
+    }
+
+    pub fn unauthorized(message: &str) -> Self {
+        Self::new(401, message)
+    }
+
+    pub fn forbidden(message: &str) -> Self {
+        Self::new(403, message)
+    }
+
+    pub fn not_found(message: &str) -> Self {
+        Self::new(404, message)
+    }
+
+    pub fn internal(message: &str) -> Self {
+        Self::new(500, message)
+    }
+
+    // Get the standard status message (reason phrase) of the status code.
+    pub fn status_message(&self) -> &'static str {
+        get_status_message(self.status_code)
+    }
+
+    // Check if the error is caused by the server (5xx), in which case the message should not be shown to the client.
+    pub fn is_server_error(&self) -> bool {
+        self.status_code >= 500
+    }
+}
+
+impl Display for HttpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {}",
+            self.status_code,
+            self.status_message(),
+            self.message
+        )
+    }
+}
+
+impl<E> From<E> for HttpError
+where
+    E: Error + 'static,
+{
+    fn from(error: E) -> Self {
+        let message = error.to_string();
+        let error: &dyn Any = &error;
+        let status_code = if let Some(error) = error.downcast_ref::<io::Error>() {
+            match error.kind() {
+                ErrorKind::NotFound => 404,
+                ErrorKind::PermissionDenied => 403,
+                ErrorKind::InvalidData | ErrorKind::InvalidInput => 400,
+                _ => 500,
+            }
+        } else if error.is::<ParseIntError>()
+            || error.is::<ParseFloatError>()
+            || error.is::<ParseBoolError>()
+            || error.is::<Utf8Error>()
+            || error.is::<FromUtf8Error>()
+        {
+            400
+        } else {
+            500
+        };
+        Self::new(status_code, &message)
+    }
+}
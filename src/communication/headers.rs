@@ -0,0 +1,236 @@
+use crate::log;
+
+// Collection of HTTP headers. Header names are matched case-insensitively, repeated headers keep all of their values and the insertion order is preserved.
+#[derive(Debug, Clone, Default)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    // Get the first value of a header.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Get all values of a header, in the order they were added.
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    // Set a header, replacing all existing values of it. Invalid headers are logged and ignored (see is_valid).
+    pub fn insert(&mut self, name: &str, value: &str) {
+        if !Self::is_valid(name, value) {
+            return;
+        }
+        match self
+            .entries
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(name))
+        {
+            // Keep the position of the first existing value.
+            Some(index) => {
+                self.remove(name);
+                self.entries
+                    .insert(index, (name.to_string(), value.to_string()));
+            }
+            None => self.append(name, value),
+        }
+    }
+
+    // Add a value to a header, keeping the existing values. Invalid headers are logged and ignored (see is_valid).
+    pub fn append(&mut self, name: &str, value: &str) {
+        if !Self::is_valid(name, value) {
+            return;
+        }
+        self.entries.push((name.to_string(), value.to_string()));
+    }
+
+    // Remove all values of a header. Returns true if the header existed.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.entries.len();
+        self.entries
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        self.entries.len() != len
+    }
+
+    // Iterate over all header names and values in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Parse a "Name: value" header line and append it. Returns false if the line is not a valid header.
+    pub fn parse_line(&mut self, line: &str) -> bool {
+        match line.split_once(':') {
+            Some((name, value)) if Self::is_valid(name, value.trim()) => {
+                self.append(name, value.trim());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Check if a header can be written as is. A line break in the name or the value would end the header early and let the rest be read as another header or as the content (response splitting).
+    pub(crate) fn is_valid(name: &str, value: &str) -> bool {
+        let is_valid = Self::is_valid_name(name) && Self::is_valid_value(value);
+        if !is_valid {
+            log!("Invalid Header: {:?}: {:?}", name, value);
+        }
+        is_valid
+    }
+
+    // Check if a header value contains no control characters other than tabs (e.g. CR or LF).
+    pub(crate) fn is_valid_value(value: &str) -> bool {
+        !value.chars().any(|c| c.is_control() && c != '\t')
+    }
+
+    fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_graphic() && !"()<>@,;:\\\"/[]?={}".contains(c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(lines: &[&str]) -> Headers {
+        let mut headers = Headers::new();
+        for line in lines {
+            assert!(headers.parse_line(line), "{line:?}");
+        }
+        headers
+    }
+
+    #[test]
+    fn parses_header_lines() {
+        let headers = parse(&["Host: example.com", "X-Empty:", "X-Padded: \t value \t"]);
+        assert_eq!(headers.get("Host"), Some("example.com"));
+        assert_eq!(headers.get("X-Empty"), Some(""));
+        assert_eq!(headers.get("X-Padded"), Some("value"));
+    }
+
+    #[test]
+    fn parses_values_without_space_after_the_colon() {
+        let headers = parse(&["Content-Type:text/plain"]);
+        assert_eq!(headers.get("Content-Type"), Some("text/plain"));
+    }
+
+    #[test]
+    fn parses_values_containing_colons() {
+        let headers = parse(&["Referer: http://example.com:8080/a", "X-Note: a: b: c"]);
+        assert_eq!(headers.get("Referer"), Some("http://example.com:8080/a"));
+        assert_eq!(headers.get("X-Note"), Some("a: b: c"));
+    }
+
+    #[test]
+    fn refuses_invalid_lines() {
+        let mut headers = Headers::new();
+        for line in [
+            "No colon",
+            ": value",
+            "Bad Name: value",
+            "Name : value",
+            "(Name): value",
+        ] {
+            assert!(!headers.parse_line(line), "{line:?}");
+        }
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn keeps_every_value_in_order() {
+        let headers = parse(&[
+            "Accept: text/html",
+            "Cookie: a=1",
+            "accept: */*",
+            "COOKIE: b=2",
+        ]);
+        assert_eq!(headers.get("Accept"), Some("text/html"));
+        assert_eq!(headers.get_all("Accept"), vec!["text/html", "*/*"]);
+        assert_eq!(headers.get_all("Cookie"), vec!["a=1", "b=2"]);
+        let names = headers.iter().map(|(name, _)| name).collect::<Vec<&str>>();
+        assert_eq!(names, vec!["Accept", "Cookie", "accept", "COOKIE"]);
+    }
+
+    #[test]
+    fn looks_up_names_ignoring_case() {
+        let mut headers = parse(&["Content-Length: 5"]);
+        assert_eq!(headers.get("content-length"), Some("5"));
+        assert_eq!(headers.get("CONTENT-LENGTH"), Some("5"));
+        assert!(headers.contains("Content-length"));
+        assert!(headers.get("Content-Type").is_none());
+        assert!(headers.remove("content-LENGTH"));
+        assert!(!headers.contains("Content-Length"));
+        assert!(!headers.remove("Content-Length"));
+    }
+
+    #[test]
+    fn insert_replaces_every_value_in_place() {
+        let mut headers = parse(&["A: 1", "Set-Cookie: a=1", "B: 2", "set-cookie: b=2"]);
+        headers.insert("Set-Cookie", "c=3");
+        let entries = headers.iter().collect::<Vec<(&str, &str)>>();
+        assert_eq!(entries, vec![("A", "1"), ("Set-Cookie", "c=3"), ("B", "2")]);
+        headers.append("Set-Cookie", "d=4");
+        assert_eq!(headers.get_all("set-cookie"), vec!["c=3", "d=4"]);
+        assert_eq!(headers.len(), 4);
+    }
+
+    #[test]
+    fn ignores_names_and_values_that_would_split_the_header() {
+        let mut headers = Headers::new();
+        headers.insert("Location", "/a\r\nSet-Cookie: evil=1");
+        headers.append("Location", "/a\nSet-Cookie: evil=1");
+        headers.append("Location", "/a\rb");
+        headers.insert("X-A\r\nSet-Cookie", "evil=1");
+        headers.append("X-A: b", "c");
+        headers.append("", "value");
+        headers.append("X-Nul", "a\0b");
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn keeps_tabs_and_other_visible_characters_in_values() {
+        let mut headers = Headers::new();
+        headers.insert("X-Tab", "a\tb");
+        headers.insert("X-Text", "été; q=\"1\"");
+        assert_eq!(headers.get("X-Tab"), Some("a\tb"));
+        assert_eq!(headers.get("X-Text"), Some("été; q=\"1\""));
+    }
+
+    #[test]
+    fn refuses_lines_with_control_characters() {
+        let mut headers = Headers::new();
+        assert!(!headers.parse_line("X-A: b\rSet-Cookie: evil=1"));
+        assert!(!headers.parse_line("X-A: b\0"));
+        assert!(headers.is_empty());
+    }
+}
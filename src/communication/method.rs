@@ -1,5 +1,6 @@
 use std::error::Error;
-use std::io::{self, ErrorKind};
+use std::io;
+use std::str::FromStr;
 
 // Representation of the supported HTTP methods.
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -10,21 +11,39 @@ pub enum Method {
     DELETE(String),
 }
 
-impl Method {
-    pub fn default() -> Self {
+impl Default for Method {
+    fn default() -> Self {
         Method::GET(String::from("GET"))
     }
+}
 
+impl FromStr for Method {
+    type Err = Box<dyn Error>;
+
+    fn from_str(method: &str) -> Result<Method, Self::Err> {
+        Method::from_str(method)
+    }
+}
+
+impl Method {
+    // Parse a method name. Kept as an inherent method, so calling Method::from_str does not require FromStr to be in scope.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(method: &str) -> Result<Method, Box<dyn Error>> {
         match method {
             "GET" => Ok(Method::GET(String::from("GET"))),
             "POST" => Ok(Method::POST(String::from("POST"))),
             "PUT" => Ok(Method::PUT(String::from("PUT"))),
             "DELETE" => Ok(Method::DELETE(String::from("DELETE"))),
-            _ => Err(Box::new(io::Error::new(
-                ErrorKind::Other,
-                "Non-supported request method",
-            ))),
+            _ => Err(Box::new(io::Error::other("Non-supported request method"))),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::GET(method)
+            | Method::POST(method)
+            | Method::PUT(method)
+            | Method::DELETE(method) => method,
         }
     }
 
@@ -32,3 +51,16 @@ impl Method {
         vec!["GET", "POST", "PUT", "DELETE"]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_methods_with_the_inherent_method_and_from_str() {
+        assert_eq!(Method::from_str("POST").unwrap().as_str(), "POST");
+        assert_eq!("DELETE".parse::<Method>().unwrap().as_str(), "DELETE");
+        assert!(Method::from_str("get").is_err());
+        assert!("PATCH".parse::<Method>().is_err());
+    }
+}
@@ -1,18 +1,46 @@
 use std::sync::Arc;
 
-use super::request::Request;
+use super::{request::Request, response::Response};
+
+// Function type of a middleware. Receives the request, the response and the continuation to the rest of the middleware chain.
+// A middleware can stop the request from reaching the route by not calling next, and can modify the response after calling next.
+pub type MiddlewareFunc = Arc<dyn Fn(&mut Request, &mut Response, Next) + Send + Sync + 'static>;
 
 #[derive(Clone)]
 pub struct Middleware {
-    pub func: Arc<dyn Fn(Option<&Middleware>, &mut Request) -> () + Send + Sync + 'static>,
+    pub func: MiddlewareFunc,
 }
 
 impl Middleware {
+    pub fn new(func: impl Fn(&mut Request, &mut Response, Next) + Send + Sync + 'static) -> Self {
+        Self {
+            func: Arc::new(func),
+        }
+    }
+}
+
+// The rest of a middleware chain, ending in the function that handles the request after all middleware (e.g. the route).
+pub struct Next<'a> {
+    middleware: &'a [Middleware],
+    endpoint: &'a dyn Fn(&mut Request, &mut Response),
+}
+
+impl<'a> Next<'a> {
     pub fn new(
-        func: impl Fn(Option<&Middleware>, &mut Request) -> () + Send + Sync + 'static + 'static,
+        middleware: &'a [Middleware],
+        endpoint: &'a dyn Fn(&mut Request, &mut Response),
     ) -> Self {
         Self {
-            func: Arc::new(func),
+            middleware,
+            endpoint,
+        }
+    }
+
+    // Execute the next middleware of the chain, or the endpoint if there is no middleware left.
+    pub fn run(self, request: &mut Request, response: &mut Response) {
+        match self.middleware.split_first() {
+            Some((mid, rest)) => (mid.func)(request, response, Next::new(rest, self.endpoint)),
+            None => (self.endpoint)(request, response),
         }
     }
 }
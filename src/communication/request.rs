@@ -1,80 +1,419 @@
 use crate::log;
-use crate::{communication::method::Method, utils::stream::read_stream_lines};
-use std::{collections::HashMap, error::Error, net::TcpStream};
+use crate::{
+    communication::{headers::Headers, method::Method},
+    utils::{
+        stream::{read_chunked_bytes, read_stream_bytes, read_stream_lines},
+        url::{parse_query, percent_decode},
+    },
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{self, BufRead, BufReader, ErrorKind},
+    net::TcpStream,
+};
 
-// Additional data about the request used only server-side.
-#[derive(Debug)]
-pub struct StaticRequestData {
-    pub path: Option<String>,
+// Size limits for incoming requests. Requests exceeding the limits are rejected with 400 Bad Request (head) or 413 Payload Too Large (body).
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    // Maximum size of the request line and headers in bytes.
+    pub max_header_size: usize,
+    // Maximum size of the (decoded) body in bytes.
+    pub max_body_size: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_header_size: 16 * 1024,
+            max_body_size: 10 * 1024 * 1024,
+        }
+    }
 }
 
 // Representation of a HTTP request.
 #[derive(Debug)]
 pub struct Request {
     pub method: Method,
+    // Path as sent by the client, still percent-encoded, see decoded_path. Values captured by route parameters (params) are decoded.
+    // It is kept encoded because routes are matched segment by segment, and an encoded "/" ("%2F") must not separate segments.
     pub path: String,
-    pub headers: HashMap<String, String>,
-    pub static_request_data: Option<StaticRequestData>,
+    pub version: String,
+    // Query string as sent by the client, without the "?". The decoded parameters are in query.
+    pub query_string: String,
+    pub query: HashMap<String, Vec<String>>,
+    pub params: HashMap<String, String>,
+    pub headers: Headers,
+    // Fields sent after a chunked body. They are kept apart from the headers, as they must not change how the request was framed or routed (e.g. Content-Length or Host).
+    pub trailers: Headers,
+    pub body: Vec<u8>,
 }
 
 impl Request {
-    pub fn build_request(stream: &TcpStream) -> Result<Request, Box<dyn Error>> {
-        let lines = read_stream_lines(stream);
-        match lines {
-            Ok(lines) => Ok(Self::get_request_struct(lines)),
-            Err(e) => return Err(e),
+    // Read the next request from the stream. The same reader must be used for every request of a connection, as it may have buffered the start of the next (pipelined) request.
+    pub fn build_request(
+        reader: &mut BufReader<&TcpStream>,
+        limits: &RequestLimits,
+    ) -> Result<Request, Box<dyn Error>> {
+        let lines = read_stream_lines(reader, limits.max_header_size)?;
+        if lines.is_empty() {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Connection closed before a request was received",
+            )));
         }
+        let mut request = Self::get_request_struct(lines)?;
+        Self::read_body(reader, &mut request, limits)?;
+        Ok(request)
     }
 
-    fn get_request_struct(lines: Vec<String>) -> Request {
-        let mut request = Request {
-            method: Method::default(),
-            path: String::new(),
-            headers: HashMap::new(),
-            static_request_data: None,
+    // Check if the client wants to keep the connection open after this request. HTTP/1.1 connections are persistent by default, HTTP/1.0 connections only with "Connection: keep-alive".
+    pub fn is_keep_alive(&self) -> bool {
+        let has_option = |option: &str| {
+            self.headers.get_all("Connection").iter().any(|value| {
+                value
+                    .split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case(option))
+            })
         };
+        match self.version.as_str() {
+            "HTTP/1.1" => !has_option("close"),
+            _ => has_option("keep-alive"),
+        }
+    }
 
-        let mut headers: Vec<String> = Vec::new();
-        for line in lines {
-            if Self::starts_with_http_request_type(&line) {
-                let (method, path) = Self::get_request_type_info(&line);
-                request.method = match Method::from_str(&method) {
-                    Ok(method) => method,
-                    Err(e) => {
-                        log!("Stream Error: {:#?}", e);
-                        return request;
-                    }
-                };
-                request.path = path;
-            } else {
-                headers.push(line);
+    // Get the path with percent-encoded characters decoded ("/a%20b" is "/a b").
+    // An encoded "/" is decoded as well, so the segments of the decoded path may differ from the segments the route was matched with.
+    pub fn decoded_path(&self) -> String {
+        percent_decode(&self.path, false)
+    }
+
+    // Get the first value of a query parameter.
+    pub fn query_param(&self, name: &str) -> Option<&String> {
+        self.query.get(name).and_then(|values| values.first())
+    }
+
+    // Read the request body following the headers. The body is either chunked (Transfer-Encoding header) or its length is given by the Content-Length header.
+    fn read_body(
+        reader: &mut impl BufRead,
+        request: &mut Request,
+        limits: &RequestLimits,
+    ) -> Result<(), Box<dyn Error>> {
+        let encodings = request.headers.get_all("Transfer-Encoding");
+        if !encodings.is_empty() {
+            // A request with both headers may be framed differently by a proxy in front of the server, so it is rejected (RFC 9112 section 6.1).
+            if request.headers.contains("Content-Length") {
+                return Err(Box::new(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Both Transfer-Encoding and Content-Length headers present",
+                )));
+            }
+            // Chunked must be the final encoding applied to a request body.
+            let is_chunked = encodings
+                .last()
+                .and_then(|encoding| encoding.rsplit(',').next())
+                .is_some_and(|last| last.trim().eq_ignore_ascii_case("chunked"));
+            if !is_chunked {
+                return Err(Box::new(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Unsupported Transfer-Encoding",
+                )));
+            }
+            let (body, trailers) = read_chunked_bytes(reader, limits.max_body_size)?;
+            request.body = body;
+            Self::parse_headers(&mut request.trailers, &trailers);
+            return Ok(());
+        }
+        if let Some(length) = Self::content_length(&request.headers)? {
+            if length > limits.max_body_size {
+                return Err(Box::new(io::Error::new(
+                    ErrorKind::FileTooLarge,
+                    "Request body exceeds the maximum allowed size",
+                )));
             }
-            for header in &headers {
-                let split = header.split(": ").collect::<Vec<&str>>();
-                if let [name, value] = split.as_slice() {
-                    request.headers.insert(name.to_string(), value.to_string());
+            request.body = read_stream_bytes(reader, length)?;
+        }
+        Ok(())
+    }
+
+    // Get the length of the body from the Content-Length headers. Every value must be a plain number, and repeated values must be equal.
+    // Anything else is rejected, as the client and the server could otherwise disagree on where the next request starts.
+    fn content_length(headers: &Headers) -> Result<Option<usize>, Box<dyn Error>> {
+        let mut content_length = None;
+        for value in headers
+            .get_all("Content-Length")
+            .iter()
+            .flat_map(|value| value.split(','))
+        {
+            let value = value.trim();
+            let length = match value.bytes().all(|byte| byte.is_ascii_digit()) {
+                true => value.parse::<usize>().ok(),
+                false => None,
+            };
+            match (length, content_length) {
+                (Some(length), None) => content_length = Some(length),
+                (Some(length), Some(previous)) if length == previous => {}
+                _ => {
+                    return Err(Box::new(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "Invalid Content-Length header",
+                    )))
                 }
             }
         }
+        Ok(content_length)
+    }
+
+    fn get_request_struct(lines: Vec<String>) -> Result<Request, Box<dyn Error>> {
+        let mut request = Request {
+            method: Method::default(),
+            path: String::new(),
+            version: String::from("HTTP/1.1"),
+            query_string: String::new(),
+            query: HashMap::new(),
+            params: HashMap::new(),
+            headers: Headers::new(),
+            trailers: Headers::new(),
+            body: Vec::new(),
+        };
+
+        // The first line is the request line, the rest are headers.
+        let Some((request_line, headers)) = lines.split_first() else {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::InvalidData,
+                "Missing request line",
+            )));
+        };
+        let Some((method, path, version)) = Self::get_request_type_info(request_line) else {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::InvalidData,
+                "Malformed request line",
+            )));
+        };
+        // Only origin-form targets ("/path?query") are served, and a missing or unknown version would leave the framing of the connection unclear.
+        if !path.starts_with('/') || !matches!(version.as_str(), "HTTP/1.0" | "HTTP/1.1") {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::InvalidData,
+                "Invalid request target or HTTP version",
+            )));
+        }
+        request.method = match method.parse::<Method>() {
+            Ok(method) => method,
+            Err(e) => {
+                log!("Stream Error: {:#?}", e);
+                return Err(Box::new(io::Error::new(
+                    ErrorKind::Unsupported,
+                    e.to_string(),
+                )));
+            }
+        };
+        request.version = version;
+        let (path, query) = path.split_once('?').unwrap_or((&path, ""));
+        request.path = path.to_string();
+        request.query_string = query.to_string();
+        request.query = parse_query(query);
+        Self::parse_headers(&mut request.headers, headers);
 
-        request
+        Ok(request)
     }
 
-    fn starts_with_http_request_type(line: &str) -> bool {
-        for http_request_type in Method::get_str_vec() {
-            if line.starts_with(http_request_type) {
-                return true;
+    // Parse "Name: value" header (or trailer) lines into the given headers.
+    fn parse_headers(headers: &mut Headers, lines: &[String]) {
+        for line in lines {
+            if !headers.parse_line(line) {
+                log!("Invalid Header: {:#?}", line);
             }
         }
-        false
     }
 
-    // Get the request type and path from the first line of the request.
-    fn get_request_type_info(line: &String) -> (String, String) {
+    // Get the request type, path and HTTP version from the first line of the request. Returns None if the line does not have exactly these three parts.
+    fn get_request_type_info(line: &str) -> Option<(String, String, String)> {
         let mut iter = line.split_whitespace();
-        (
-            iter.next().unwrap_or_default().to_string(),
-            iter.next().unwrap_or_default().to_string(),
-        )
+        let info = (
+            iter.next()?.to_string(),
+            iter.next()?.to_string(),
+            iter.next()?.to_string(),
+        );
+        iter.next().is_none().then_some(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parse the head lines and read the body from the rest of the input, as build_request does for a stream.
+    fn parse(head: &[&str], body: &str) -> Result<Request, Box<dyn Error>> {
+        let lines = head.iter().map(|line| line.to_string()).collect();
+        let mut request = Request::get_request_struct(lines)?;
+        Request::read_body(
+            &mut body.as_bytes(),
+            &mut request,
+            &RequestLimits::default(),
+        )?;
+        Ok(request)
+    }
+
+    #[test]
+    fn reads_chunked_bodies() {
+        let head = ["POST /upload HTTP/1.1", "Transfer-Encoding: chunked"];
+        let request = parse(&head, "2\r\nhi\r\n3;ext=1\r\n!!!\r\n0\r\n\r\n").unwrap();
+        assert_eq!(request.body, b"hi!!!");
+        assert!(request.trailers.is_empty());
+    }
+
+    #[test]
+    fn keeps_trailers_apart_from_headers() {
+        let head = [
+            "POST /upload HTTP/1.1",
+            "Host: example.com",
+            "Transfer-Encoding: chunked",
+        ];
+        let body =
+            "2\r\nhi\r\n0\r\nContent-Length: 99\r\nHost: evil.com\r\nX-Checksum: abc\r\n\r\n";
+        let request = parse(&head, body).unwrap();
+        assert_eq!(request.body, b"hi");
+        assert_eq!(request.headers.get("Content-Length"), None);
+        assert_eq!(request.headers.get("Host"), Some("example.com"));
+        assert_eq!(request.headers.get("X-Checksum"), None);
+        assert_eq!(request.trailers.get("Content-Length"), Some("99"));
+        assert_eq!(request.trailers.get("x-checksum"), Some("abc"));
+    }
+
+    fn error_kind(result: Result<Request, Box<dyn Error>>) -> ErrorKind {
+        let error = result.unwrap_err();
+        error.downcast_ref::<io::Error>().unwrap().kind()
+    }
+
+    #[test]
+    fn rejects_transfer_encoding_with_content_length() {
+        let head = [
+            "POST /upload HTTP/1.1",
+            "Transfer-Encoding: chunked",
+            "Content-Length: 2",
+        ];
+        let result = parse(&head, "2\r\nhi\r\n0\r\n\r\n");
+        assert_eq!(error_kind(result), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_bodies_not_ending_with_chunked() {
+        for encoding in ["gzip", "chunked, gzip", "chunkedx"] {
+            let head = [
+                "POST /upload HTTP/1.1",
+                &format!("Transfer-Encoding: {encoding}"),
+            ];
+            let result = parse(&head, "2\r\nhi\r\n0\r\n\r\n");
+            assert_eq!(error_kind(result), ErrorKind::InvalidData, "{encoding:?}");
+        }
+        let head = ["POST /upload HTTP/1.1", "Transfer-Encoding: gzip, Chunked"];
+        assert!(parse(&head, "0\r\n\r\n").is_ok());
+    }
+
+    #[test]
+    fn keeps_the_path_encoded_and_decodes_on_request() {
+        let request = parse(&["GET /files/a%20b/c%2Fd?q=%20 HTTP/1.1"], "").unwrap();
+        assert_eq!(request.path, "/files/a%20b/c%2Fd");
+        assert_eq!(request.decoded_path(), "/files/a b/c/d");
+        assert_eq!(request.query_string, "q=%20");
+        assert_eq!(request.query_param("q").map(String::as_str), Some(" "));
+    }
+
+    #[test]
+    fn parses_request_lines() {
+        let request = parse(&["DELETE /users/1?force=true HTTP/1.0"], "").unwrap();
+        assert_eq!(request.method.as_str(), "DELETE");
+        assert_eq!(request.path, "/users/1");
+        assert_eq!(request.version, "HTTP/1.0");
+        assert_eq!(
+            request.query_param("force").map(String::as_str),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_request_lines() {
+        for line in [
+            "GET",
+            "GET /test",
+            "GET /test HTTP/1.1 extra",
+            "GET test HTTP/1.1",
+            "GET * HTTP/1.1",
+            "GET http://example.com/ HTTP/1.1",
+            "GET /test HTTP/2.0",
+            "GET /test HTTP/1.1x",
+            "GET /test http/1.1",
+        ] {
+            assert_eq!(
+                error_kind(parse(&[line], "")),
+                ErrorKind::InvalidData,
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_methods() {
+        let result = parse(&["PATCH /test HTTP/1.1"], "");
+        assert_eq!(error_kind(result), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn reads_bodies_by_content_length() {
+        let head = ["POST /upload HTTP/1.1", "Content-Length: 5"];
+        assert_eq!(parse(&head, "hello, next request").unwrap().body, b"hello");
+        assert!(parse(&["GET / HTTP/1.1"], "ignored")
+            .unwrap()
+            .body
+            .is_empty());
+    }
+
+    #[test]
+    fn accepts_repeated_equal_content_lengths() {
+        let head = ["POST / HTTP/1.1", "Content-Length: 5", "Content-Length: 5"];
+        assert_eq!(parse(&head, "hello").unwrap().body, b"hello");
+        let head = ["POST / HTTP/1.1", "Content-Length: 5, 5"];
+        assert_eq!(parse(&head, "hello").unwrap().body, b"hello");
+    }
+
+    #[test]
+    fn rejects_conflicting_content_lengths() {
+        let head = ["POST / HTTP/1.1", "Content-Length: 5", "Content-Length: 6"];
+        assert_eq!(error_kind(parse(&head, "hello!")), ErrorKind::InvalidData);
+        let head = ["POST / HTTP/1.1", "Content-Length: 5, 6"];
+        assert_eq!(error_kind(parse(&head, "hello!")), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_malformed_content_lengths() {
+        for value in [
+            "",
+            "+5",
+            "-5",
+            "5.0",
+            "0x5",
+            "five",
+            "5 5",
+            "99999999999999999999999",
+        ] {
+            let head = ["POST / HTTP/1.1", &format!("Content-Length: {value}")];
+            assert_eq!(
+                error_kind(parse(&head, "hello")),
+                ErrorKind::InvalidData,
+                "{value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bodies_over_the_size_limit() {
+        let head = ["POST / HTTP/1.1", "Content-Length: 20000000"];
+        assert_eq!(error_kind(parse(&head, "")), ErrorKind::FileTooLarge);
+    }
+
+    #[test]
+    fn rejects_truncated_bodies() {
+        let head = ["POST / HTTP/1.1", "Content-Length: 10"];
+        assert_eq!(error_kind(parse(&head, "short")), ErrorKind::UnexpectedEof);
     }
 }
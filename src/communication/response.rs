@@ -1,13 +1,57 @@
-use crate::utils::guess::guess_mime_type;
-use std::{io::Error, io::Write, net::TcpStream};
+use crate::communication::chunked::{ChunkedFunc, ChunkedWriter};
+use crate::communication::headers::Headers;
+use crate::communication::sse::{event_stream, write_events, EventSender};
+use crate::server::shutdown::ShutdownHandle;
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::{self, Error, ErrorKind, Read, Write},
+    net::TcpStream,
+};
+
+// Size of the chunks a streamed body is copied to the client in.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+// Content of a response, either held in memory or read from a reader (e.g. a file) while the response is sent.
+pub enum Body {
+    Bytes(Vec<u8>),
+    // A reader and the amount of bytes it provides, sent as the Content-Length.
+    Stream(Box<dyn Read + Send>, u64),
+    // A function writing the content over time, sent with Transfer-Encoding: chunked.
+    Chunked(ChunkedFunc),
+}
+
+// How the end of the content is communicated to the client.
+enum Framing {
+    Length(u64),
+    Chunked,
+    // The content ends when the connection is closed, for clients that do not support chunked responses.
+    Close,
+}
+
+impl Debug for Body {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Bytes(bytes) => f.debug_tuple("Bytes").field(bytes).finish(),
+            Body::Stream(_, length) => f.debug_tuple("Stream").field(length).finish(),
+            Body::Chunked(_) => f.write_str("Chunked"),
+        }
+    }
+}
 
 // Representation of a HTTP response.
 #[derive(Debug)]
 pub struct Response {
     pub status_code: usize,
     pub status_message: String,
+    pub headers: Headers,
     pub content_type: Option<String>,
-    pub content: Option<String>,
+    pub content: Option<Body>,
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Response {
@@ -15,6 +59,7 @@ impl Response {
         Self {
             status_code: 200,
             status_message: String::from("OK"),
+            headers: Headers::new(),
             content_type: None,
             content: None,
         }
@@ -22,50 +67,161 @@ impl Response {
 
     // Send this response back to the client.
     pub fn send(&mut self, stream: &TcpStream) -> Result<(), Error> {
+        self.send_encoded(stream, true, None)
+    }
+
+    // Send this response from the server. For clients that do not support chunked responses (HTTP/1.0), chunked content is sent as is, so the connection must be closed after it.
+    // Chunked content can check the shutdown handle to end early when the server shuts down, see ChunkedWriter::is_shutdown.
+    pub(crate) fn send_from_server(
+        &mut self,
+        stream: &TcpStream,
+        chunked: bool,
+        shutdown: &ShutdownHandle,
+    ) -> Result<(), Error> {
+        self.send_encoded(stream, chunked, Some(shutdown))
+    }
+
+    // Check if the content is written over time with chunked transfer-encoding, see set_chunked.
+    pub fn is_chunked(&self) -> bool {
+        matches!(self.content, Some(Body::Chunked(_)))
+    }
+
+    fn send_encoded(
+        &mut self,
+        stream: &TcpStream,
+        chunked: bool,
+        shutdown: Option<&ShutdownHandle>,
+    ) -> Result<(), Error> {
+        // The response ends after the head for bodiless status codes, so content set for them is dropped.
+        if self.is_bodiless() {
+            self.content = None;
+        }
         match (&self.content_type, &self.content) {
             // If the content type is set, but the content is not, send the content type.
             (Some(content_type), Some(_)) => {
-                self.send_with_content(&stream, content_type)?;
-                return Ok(());
+                let content_type = content_type.clone();
+                self.send_with_content(stream, &content_type, chunked, shutdown)
             }
-            // If the content type is not set, but the content is, guess the content type and send it.
+            // If the content type is not set, but the content is, use the Content-Type header or guess the content type and send it.
             (None, Some(content)) => {
-                let content_type = match &self.content_type {
-                    Some(content_type) => content_type.clone(),
-                    None => guess_mime_type(content.as_str()),
+                let content_type = match self.headers.get("Content-Type") {
+                    Some(content_type) => content_type.to_string(),
+                    None => match content {
+                        Body::Bytes(bytes) if std::str::from_utf8(bytes).is_ok() => {
+                            String::from("text/plain")
+                        }
+                        _ => String::from("application/octet-stream"),
+                    },
                 };
-                self.send_with_content(&stream, &content_type)?;
-                return Ok(());
+                self.send_with_content(stream, &content_type, chunked, shutdown)
             }
             // If neither the content type nor the content is set, send no content.
-            _ => {
-                self.send_without_content(&stream)?;
-                return Ok(());
+            _ => self.send_without_content(stream),
+        }
+    }
+
+    fn send_with_content(
+        &mut self,
+        mut stream: &TcpStream,
+        content_type: &str,
+        chunked: bool,
+        shutdown: Option<&ShutdownHandle>,
+    ) -> Result<(), Error> {
+        match self.content.take().unwrap() {
+            Body::Bytes(content) => {
+                let framing = Framing::Length(content.len() as u64);
+                let head = self.format_head(Some(content_type), framing);
+                stream.write_all(head.as_bytes())?;
+                stream.write_all(&content)
+            }
+            Body::Stream(reader, length) => {
+                let head = self.format_head(Some(content_type), Framing::Length(length));
+                stream.write_all(head.as_bytes())?;
+                Self::copy_stream(reader, length, stream)
+            }
+            Body::Chunked(func) => {
+                let framing = if chunked {
+                    Framing::Chunked
+                } else {
+                    Framing::Close
+                };
+                let head = self.format_head(Some(content_type), framing);
+                stream.write_all(head.as_bytes())?;
+                let mut writer = ChunkedWriter::new(stream, chunked, shutdown.cloned());
+                (func)(&mut writer)?;
+                writer.finish()
             }
         }
     }
 
-    fn send_with_content(&self, mut stream: &TcpStream, content_type: &str) -> Result<(), Error> {
-        let status_code = &self.status_code;
-        let status_message = &self.status_message;
-        let content = &self.content.as_ref().unwrap();
-        let content_length = content.len();
-
-        let format = format!(
-            "HTTP/1.1 {status_code} {status_message}\r\n\
-             Content-Type: {content_type}\r\n\
-             Content-Length: {content_length}\r\n\
-             \r\n\
-             {content}",
-        );
-        stream.write_all(format.as_bytes())
+    // Copy the body from the reader to the client in fixed-size chunks. Fails if the reader ends before the announced length, as the response can then not be completed.
+    fn copy_stream(
+        reader: Box<dyn Read + Send>,
+        length: u64,
+        mut stream: &TcpStream,
+    ) -> Result<(), Error> {
+        let mut reader = reader.take(length);
+        let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+        let mut remaining = length;
+        while remaining > 0 {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => {
+                    return Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "Response body ended before its Content-Length",
+                    ))
+                }
+                Ok(read) => read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            stream.write_all(&buffer[..read])?;
+            remaining -= read as u64;
+        }
+        Ok(())
     }
 
     fn send_without_content(&self, mut stream: &TcpStream) -> Result<(), Error> {
+        let head = self.format_head(None, Framing::Length(0));
+        stream.write_all(head.as_bytes())
+    }
+
+    // Format the status line and headers of the response, including the terminating empty line.
+    fn format_head(&self, content_type: Option<&str>, framing: Framing) -> String {
+        // The status message and the content type are not set through Headers, so control characters (e.g. line breaks) are removed here.
+        let strip_controls = |value: &str| value.replace(|c: char| c.is_control(), "");
         let status_code = &self.status_code;
-        let status_message = &self.status_message;
-        let format = format!("HTTP/1.1 {status_code} {status_message}\r\n\"",);
-        stream.write_all(format.as_bytes())
+        let status_message = strip_controls(&self.status_message);
+        let mut head = format!("HTTP/1.1 {status_code} {status_message}\r\n");
+        if let Some(content_type) = content_type.map(strip_controls) {
+            head.push_str(&format!("Content-Type: {content_type}\r\n"));
+        }
+        let is_chunked = matches!(framing, Framing::Chunked);
+        match framing {
+            Framing::Length(_) if self.is_bodiless() => {}
+            Framing::Length(content_length) => {
+                head.push_str(&format!("Content-Length: {content_length}\r\n"))
+            }
+            Framing::Chunked => head.push_str("Transfer-Encoding: chunked\r\n"),
+            Framing::Close => {}
+        }
+        // Content headers are computed from the content, so the user set ones are skipped.
+        // Trailers can only follow chunked content, so the Trailer header announcing them is skipped otherwise (e.g. for HTTP/1.0 clients).
+        for (name, value) in self.headers.iter().filter(|(name, _)| {
+            !name.eq_ignore_ascii_case("Content-Type")
+                && !name.eq_ignore_ascii_case("Content-Length")
+                && !name.eq_ignore_ascii_case("Transfer-Encoding")
+                && (is_chunked || !name.eq_ignore_ascii_case("Trailer"))
+        }) {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+        head
+    }
+
+    // Check if the status code never has content (1xx, 204 No Content and 304 Not Modified), in which case neither content nor Content-Length is sent.
+    fn is_bodiless(&self) -> bool {
+        matches!(self.status_code, 100..=199 | 204 | 304)
     }
 
     pub fn set_status(&mut self, status_code: usize, status_message: &str) {
@@ -81,9 +237,71 @@ impl Response {
         self.status_message = status_message.to_string();
     }
 
+    // Set a response header, replacing any existing values of it.
+    pub fn insert_header(&mut self, name: &str, value: &str) {
+        self.headers.insert(name, value);
+    }
+
+    // Add a value to a response header, keeping the existing values (e.g. multiple Set-Cookie headers).
+    pub fn append_header(&mut self, name: &str, value: &str) {
+        self.headers.append(name, value);
+    }
+
+    // Remove all values of a response header. Returns true if the header existed.
+    pub fn remove_header(&mut self, name: &str) -> bool {
+        self.headers.remove(name)
+    }
+
     pub fn set_contents(&mut self, content_type: &str, content: &str) {
+        self.set_bytes(content_type, content.as_bytes().to_vec());
+    }
+
+    // Set raw bytes (e.g. an image) as the content of the response.
+    pub fn set_bytes(&mut self, content_type: &str, content: Vec<u8>) {
+        self.content_type = Some(content_type.to_string());
+        self.content = Some(Body::Bytes(content));
+    }
+
+    // Set content read from the reader (e.g. a file) while the response is sent, instead of holding it in memory. The reader must provide length bytes.
+    pub fn set_stream(
+        &mut self,
+        content_type: &str,
+        reader: impl Read + Send + 'static,
+        length: u64,
+    ) {
+        self.content_type = Some(content_type.to_string());
+        self.content = Some(Body::Stream(Box::new(reader), length));
+    }
+
+    // Set a function writing the content while the response is sent, so the content can be produced over time (e.g. a long export).
+    // The content is sent in chunks with Transfer-Encoding: chunked, see ChunkedWriter. Trailers added with ChunkedWriter::trailer should be announced with the Trailer header.
+    pub fn set_chunked<F>(&mut self, content_type: &str, func: F)
+    where
+        F: FnOnce(&mut ChunkedWriter) -> io::Result<()> + Send + 'static,
+    {
         self.content_type = Some(content_type.to_string());
-        self.content = Some(content.to_string());
+        self.content = Some(Body::Chunked(Box::new(func)));
+    }
+
+    // Respond with an event stream (Server-Sent Events). Events sent with the returned sender, e.g. from another thread, are written to the client as they arrive.
+    // The stream keeps the connection and a worker of the server busy until every sender has been dropped, the client disconnects or the server shuts down.
+    pub fn set_event_stream(&mut self) -> EventSender {
+        let (sender, receiver) = event_stream();
+        self.insert_header("Cache-Control", "no-cache");
+        self.set_chunked("text/event-stream", move |writer| {
+            write_events(receiver, writer)
+        });
+        sender
+    }
+
+    // Set UTF-8 text as the content of the response.
+    pub fn set_text(&mut self, content: &str) {
+        self.set_contents("text/plain; charset=utf-8", content);
+    }
+
+    // Set an already serialized JSON document as the content of the response.
+    pub fn set_json(&mut self, content: &str) {
+        self.set_contents("application/json", content);
     }
 
     pub fn set_content_type(&mut self, content_type: &str) {
@@ -91,6 +309,46 @@ impl Response {
     }
 
     pub fn set_content(&mut self, content: &str) {
-        self.content = Some(content.to_string());
+        self.content = Some(Body::Bytes(content.as_bytes().to_vec()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_line_breaks_from_the_status_message_and_content_type() {
+        let mut response = Response::new();
+        response.set_status(302, "Found\r\nSet-Cookie: evil=1");
+        let head = response.format_head(Some("text/plain\r\nX-Evil: 1"), Framing::Length(2));
+        assert!(head.starts_with("HTTP/1.1 302 FoundSet-Cookie: evil=1\r\n"));
+        assert!(head.contains("Content-Type: text/plainX-Evil: 1\r\n"));
+        assert_eq!(head.matches("\r\n").count(), 4);
+    }
+
+    #[test]
+    fn does_not_send_headers_that_would_split_the_response() {
+        let mut response = Response::new();
+        response.insert_header("Location", "/a\r\nSet-Cookie: evil=1");
+        response.append_header("X-A\r\nSet-Cookie", "evil=1");
+        response.insert_header("X-Ok", "1");
+        let head = response.format_head(None, Framing::Length(0));
+        assert_eq!(
+            head,
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nX-Ok: 1\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn announces_trailers_only_for_chunked_content() {
+        let mut response = Response::new();
+        response.insert_header("Trailer", "X-Row-Count");
+        let head = response.format_head(Some("text/csv"), Framing::Chunked);
+        assert!(head.contains("Transfer-Encoding: chunked\r\nTrailer: X-Row-Count\r\n"));
+        let head = response.format_head(Some("text/csv"), Framing::Close);
+        assert!(!head.contains("Trailer"));
+        let head = response.format_head(Some("text/csv"), Framing::Length(3));
+        assert!(!head.contains("Trailer"));
     }
 }
@@ -1,22 +1,119 @@
 use std::{collections::HashMap, sync::Arc};
 
-use super::{method::Method, request::Request, response::Response};
+use crate::log;
+
+use super::{
+    error::HttpError, method::Method, middleware::Middleware, request::Request, response::Response,
+};
 
 // User defined function type found at every defined route (path)
-pub type RouteFunc = Arc<dyn Fn(&Request, &mut Response) -> () + Send + Sync + 'static>;
+pub type RouteFunc =
+    Arc<dyn Fn(&Request, &mut Response) -> Result<(), HttpError> + Send + Sync + 'static>;
+
+// User defined function type that turns an error returned from a route function into a response.
+pub type ErrorFunc = Arc<dyn Fn(&HttpError, &Request, &mut Response) + Send + Sync + 'static>;
+
+// User defined functions called when a request does not match any route (not found) or any method of a route (method not allowed), or when a route function returns an error.
+#[derive(Clone, Default)]
+pub struct Fallbacks {
+    pub not_found: Option<RouteFunc>,
+    pub method_not_allowed: Option<RouteFunc>,
+    pub error: Option<ErrorFunc>,
+}
+
+impl Fallbacks {
+    // Combine with fallbacks of an outer scope (e.g. the parent router), functions set on self take precedence.
+    pub fn or(&self, outer: &Fallbacks) -> Fallbacks {
+        Fallbacks {
+            not_found: self.not_found.clone().or(outer.not_found.clone()),
+            method_not_allowed: self
+                .method_not_allowed
+                .clone()
+                .or(outer.method_not_allowed.clone()),
+            error: self.error.clone().or(outer.error.clone()),
+        }
+    }
+
+    // Call a route function, passing a returned error to the error function.
+    pub fn call(&self, func: &RouteFunc, request: &Request, response: &mut Response) {
+        if let Err(error) = (func)(request, response) {
+            self.error(&error, request, response);
+        }
+    }
+
+    // Call the user registered error function, or respond with the status code of the error. The message of server errors is only logged.
+    pub fn error(&self, error: &HttpError, request: &Request, response: &mut Response) {
+        log!("Route Error: {}", error);
+        match &self.error {
+            Some(func) => (func)(error, request, response),
+            None => {
+                response.set_status(error.status_code, error.status_message());
+                match error.is_server_error() {
+                    true => response.set_text(error.status_message()),
+                    false => response.set_text(&error.message),
+                }
+            }
+        }
+    }
+
+    // Call the user registered not found function, or respond with the default 404 Not Found.
+    pub fn not_found(&self, request: &Request, response: &mut Response) {
+        match &self.not_found {
+            Some(func) => self.call(func, request, response),
+            None => {
+                response.set_status(404, "Not Found");
+                response.set_text("Not Found");
+            }
+        }
+    }
+
+    // Call the user registered method not allowed function, or respond with the default 405 Method Not Allowed.
+    pub fn method_not_allowed(&self, request: &Request, response: &mut Response) {
+        match &self.method_not_allowed {
+            Some(func) => self.call(func, request, response),
+            None => {
+                response.set_status(405, "Method Not Allowed");
+                response.set_text("Method Not Allowed");
+            }
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Route {
     pub method_map: HashMap<Method, RouteFunc>,
+    pub middleware: Vec<Middleware>,
+    pub method_middleware: HashMap<Method, Vec<Middleware>>,
 }
 
 impl Route {
     pub fn new(method_map: Option<HashMap<Method, RouteFunc>>) -> Self {
-        match method_map {
-            Some(m) => Self { method_map: m },
-            None => Self {
-                method_map: HashMap::new(),
-            },
+        Self {
+            method_map: method_map.unwrap_or_default(),
+            middleware: Vec::new(),
+            method_middleware: HashMap::new(),
+        }
+    }
+
+    // Get the middleware executed before the function of the given method, route middleware first and then method middleware.
+    pub fn middleware_for(&self, method: &Method) -> Vec<Middleware> {
+        let mut middleware = self.middleware.clone();
+        if let Some(method_middleware) = self.method_middleware.get(method) {
+            middleware.extend(method_middleware.iter().cloned());
         }
+        middleware
+    }
+
+    // Get the methods registered for this route as a comma separated list, as used by the Allow header.
+    pub fn allowed_methods(&self) -> String {
+        Method::get_str_vec()
+            .into_iter()
+            .filter(|method| {
+                self.method_map
+                    .keys()
+                    .any(|registered| registered.as_str() == *method)
+            })
+            .collect::<Vec<&str>>()
+            .join(", ")
     }
 }
@@ -6,94 +6,189 @@ use std::{
 use crate::{ds::trie::Trie, log};
 
 use super::{
+    error::HttpError,
     method::Method,
-    middleware::Middleware,
+    middleware::{Middleware, Next},
     request::Request,
     response::Response,
-    route::{Route, RouteFunc},
+    route::{Fallbacks, Route, RouteFunc},
 };
 
 #[derive(Clone)]
 pub struct Router {
     pub base_path: String,
+    pub fallbacks: Fallbacks,
     middleware: Vec<Middleware>,
     routes: Arc<Mutex<Trie<Route>>>,
+    routers: Arc<Mutex<Trie<Router>>>,
 }
 
 impl Router {
     pub fn new(base_path: &str) -> Self {
         Self {
             base_path: String::from(base_path),
+            fallbacks: Fallbacks::default(),
             middleware: Vec::new(),
             routes: Arc::new(Mutex::new(Trie::new())),
+            routers: Arc::new(Mutex::new(Trie::new())),
         }
     }
 
     // Create a route for the router.
     pub fn route<F>(&mut self, path: &str, method: &str, func: F)
     where
-        F: Fn(&Request, &mut Response) -> () + Send + Sync + 'static,
+        F: Fn(&Request, &mut Response) -> Result<(), HttpError> + Send + Sync + 'static,
     {
         self.create_route(path, method, func);
     }
 
-    // Register a middleware with the router.
+    // Register a function called when no route of the router matches the request path. Overrides the not found function of the server.
+    pub fn not_found<F>(&mut self, func: F)
+    where
+        F: Fn(&Request, &mut Response) -> Result<(), HttpError> + Send + Sync + 'static,
+    {
+        self.fallbacks.not_found = Some(Arc::new(func));
+    }
+
+    // Register a function called when a route of the router matches the request path, but not the request method. Overrides the method not allowed function of the server.
+    pub fn method_not_allowed<F>(&mut self, func: F)
+    where
+        F: Fn(&Request, &mut Response) -> Result<(), HttpError> + Send + Sync + 'static,
+    {
+        self.fallbacks.method_not_allowed = Some(Arc::new(func));
+    }
+
+    // Mount a router under this router. The mounted router handles every path starting with this router's base path + prefix + its own base path.
+    // Middleware of this router is executed before the middleware of the mounted router.
+    pub fn mount(&mut self, prefix: &str, router: Router) {
+        let path = format!("{}{}", prefix, router.base_path);
+        self.routers.lock().unwrap().insert(path.as_str(), router);
+    }
+
+    // Register a middleware with the router. Middleware is executed in registration order and wraps the handling of the request (see Next).
     pub fn middleware<F>(&mut self, func: F)
     where
-        F: Fn(Option<&Middleware>, &mut Request) -> () + Send + Sync + 'static,
+        F: Fn(&mut Request, &mut Response, Next) + Send + Sync + 'static,
     {
         self.middleware.push(Middleware::new(func));
     }
 
-    // Find a route for the router given a request. Returns None if no route is found.
-    pub fn find_route(&self, request: &mut Request) -> Option<Route> {
-        self.routes.lock().unwrap().search(&request.path)
+    // Register a middleware executed only for requests matching the route at the path, after the middleware of the router.
+    pub fn route_middleware<F>(&mut self, path: &str, func: F)
+    where
+        F: Fn(&mut Request, &mut Response, Next) + Send + Sync + 'static,
+    {
+        self.modify_route(path, |route| route.middleware.push(Middleware::new(func)));
     }
 
-    pub fn execute_middleware(&self, request: &mut Request) {
-        let mut prev_mid: Option<&Middleware> = None;
-        if self.middleware.len() > 0 {
-            for mid in &self.middleware {
-                (mid.func)(prev_mid, request);
-                prev_mid = Some(mid);
-            }
+    // Register a middleware executed only for requests matching the route at the path and the method, after the middleware of the route.
+    pub fn method_middleware<F>(&mut self, path: &str, method: &str, func: F)
+    where
+        F: Fn(&mut Request, &mut Response, Next) + Send + Sync + 'static,
+    {
+        match method.parse::<Method>() {
+            Ok(method) => self.modify_route(path, |route| {
+                route
+                    .method_middleware
+                    .entry(method)
+                    .or_default()
+                    .push(Middleware::new(func))
+            }),
+            Err(e) => log!("Method Error: {:#?}", e),
         }
     }
 
-    // Register a route with the router.
-    fn create_route<F>(&mut self, path: &str, method: &str, func: F)
-    where
-        F: Fn(&Request, &mut Response) -> () + Send + Sync + 'static,
-    {
-        match Method::from_str(method) {
-            Ok(method) => {
-                let mut routes = self.routes.lock().unwrap();
-                let path = format!("{}{}", self.base_path, path);
-                match routes.search(path.as_str()) {
-                    Some(route) => {
-                        let mut method_map = route.method_map;
-                        assert!(
-                            !method_map.contains_key(&method),
-                            "Route already exists for path: {} and method: {:#?}",
-                            path,
-                            method
-                        );
-                        Self::insert_route(&mut method_map, method, func);
+    // Handle a request, where path is the part of the request path after the base path of this router.
+    // Mounted routers handle their whole subtree, otherwise the function of the matching route is called. Responds with 404 Not Found if no route matches, or 405 Method Not Allowed if the route has no function for the request method.
+    pub fn handle(
+        &self,
+        path: &str,
+        fallbacks: &Fallbacks,
+        request: &mut Request,
+        response: &mut Response,
+    ) {
+        let fallbacks = self.fallbacks.or(fallbacks);
+        let endpoint = |request: &mut Request, response: &mut Response| {
+            self.dispatch(path, &fallbacks, request, response)
+        };
+        Next::new(&self.middleware, &endpoint).run(request, response);
+    }
+
+    // Dispatch a request to a mounted router or a route of this router, after the middleware of this router has been executed.
+    fn dispatch(
+        &self,
+        path: &str,
+        fallbacks: &Fallbacks,
+        request: &mut Request,
+        response: &mut Response,
+    ) {
+        let router = self.routers.lock().unwrap().search_prefix(path);
+        if let Some((router, params, path)) = router {
+            request.params.extend(params);
+            router.handle(&path, fallbacks, request, response);
+            return;
+        }
+        match self.find_route(path, request) {
+            // A route with only middleware registered is treated as if it did not exist.
+            Some(route) if !route.method_map.is_empty() => {
+                match route.method_map.get(&request.method) {
+                    Some(func) => {
+                        let middleware = route.middleware_for(&request.method);
+                        let endpoint = |request: &mut Request, response: &mut Response| {
+                            fallbacks.call(func, request, response)
+                        };
+                        Next::new(&middleware, &endpoint).run(request, response);
                     }
                     None => {
-                        let mut method_map = HashMap::new();
-                        Self::insert_route(&mut method_map, method, func);
-                        routes.insert(path.as_str(), Route::new(Some(method_map)));
+                        response.insert_header("Allow", &route.allowed_methods());
+                        fallbacks.method_not_allowed(request, response);
                     }
                 }
             }
+            _ => fallbacks.not_found(request, response),
+        }
+    }
+
+    // Find a route for the router given a path relative to the base path, and store the path parameters of the route in the request. Returns None if no route is found.
+    pub fn find_route(&self, path: &str, request: &mut Request) -> Option<Route> {
+        let (route, params) = self.routes.lock().unwrap().search_params(path)?;
+        request.params.extend(params);
+        Some(route)
+    }
+
+    // Register a route with the router.
+    fn create_route<F>(&mut self, path: &str, method: &str, func: F)
+    where
+        F: Fn(&Request, &mut Response) -> Result<(), HttpError> + Send + Sync + 'static,
+    {
+        match method.parse::<Method>() {
+            Ok(method) => self.modify_route(path, |route| {
+                assert!(
+                    !route.method_map.contains_key(&method),
+                    "Route already exists for path: {} and method: {:#?}",
+                    path,
+                    method
+                );
+                Self::insert_route(&mut route.method_map, method, func);
+            }),
             Err(e) => log!("Method Error: {:#?}", e),
         }
     }
 
+    // Modify the route registered at the path, creating an empty route if it does not exist yet.
+    fn modify_route(&mut self, path: &str, modify: impl FnOnce(&mut Route)) {
+        let mut routes = self.routes.lock().unwrap();
+        if routes.get_mut(path).is_none() {
+            routes.insert(path, Route::new(None));
+        }
+        if let Some(route) = routes.get_mut(path) {
+            modify(route);
+        }
+    }
+
     fn insert_route<F>(method_map: &mut HashMap<Method, RouteFunc>, method: Method, func: F)
     where
-        F: Fn(&Request, &mut Response) -> () + Send + Sync + 'static,
+        F: Fn(&Request, &mut Response) -> Result<(), HttpError> + Send + Sync + 'static,
     {
         method_map.insert(method, Arc::new(func));
     }
@@ -0,0 +1,195 @@
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use super::chunked::ChunkedWriter;
+
+// How often a comment is sent on an idle event stream, which keeps proxies from closing the connection and detects disconnected clients.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+// How often an idle event stream checks if the server is shutting down.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// A server-sent event, sent to the client as a text/event-stream frame.
+#[derive(Debug, Clone, Default)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: String,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    // Create an event with the data, which may span multiple lines.
+    pub fn new(data: &str) -> Self {
+        Self {
+            data: data.to_string(),
+            ..Self::default()
+        }
+    }
+
+    // Set the id of the event, sent back by the client in Last-Event-ID when it reconnects.
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    // Set the type of the event, dispatched to the listeners of that type on the client ("message" by default).
+    pub fn event(mut self, event: &str) -> Self {
+        self.event = Some(event.to_string());
+        self
+    }
+
+    // Set how long the client waits before reconnecting when the connection is lost.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    // Format the event as a frame. Line breaks in the id and type would end the field early, so they are removed.
+    fn format(&self) -> String {
+        let single_line = |value: &str| value.replace(['\r', '\n'], "");
+        let mut frame = String::new();
+        if let Some(id) = &self.id {
+            frame.push_str(&format!("id: {}\n", single_line(id)));
+        }
+        if let Some(event) = &self.event {
+            frame.push_str(&format!("event: {}\n", single_line(event)));
+        }
+        if let Some(retry) = &self.retry {
+            frame.push_str(&format!("retry: {}\n", retry.as_millis()));
+        }
+        // Clients end a line at "\r\n", "\r" or "\n", so each of them starts a new data field.
+        for line in self.data.replace("\r\n", "\n").split(['\r', '\n']) {
+            frame.push_str(&format!("data: {}\n", line));
+        }
+        frame.push('\n');
+        frame
+    }
+}
+
+// Sending side of an event stream, see Response::set_event_stream. Can be cloned and sent to other threads.
+// The stream ends when every sender has been dropped.
+#[derive(Debug, Clone)]
+pub struct EventSender {
+    sender: Sender<Event>,
+}
+
+impl EventSender {
+    // Send an event to the client. Returns false if the client has disconnected, in which case the sender should be dropped.
+    pub fn send(&self, event: Event) -> bool {
+        self.sender.send(event).is_ok()
+    }
+}
+
+// Create an event stream, returning the sender for the handler and the receiver written to the client by the server.
+pub(crate) fn event_stream() -> (EventSender, Receiver<Event>) {
+    let (sender, receiver) = mpsc::channel();
+    (EventSender { sender }, receiver)
+}
+
+// Write events to the client as they are received, until every sender has been dropped, the client disconnects (the write fails) or the server shuts down.
+pub(crate) fn write_events(
+    receiver: Receiver<Event>,
+    writer: &mut ChunkedWriter,
+) -> io::Result<()> {
+    let mut last_write = Instant::now();
+    while !writer.is_shutdown() {
+        match receiver.recv_timeout(SHUTDOWN_POLL_INTERVAL) {
+            Ok(event) => writer.write_all(event.format().as_bytes())?,
+            Err(RecvTimeoutError::Timeout) if last_write.elapsed() >= KEEP_ALIVE_INTERVAL => {
+                writer.write_all(b": keep-alive\n\n")?
+            }
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        writer.flush()?;
+        last_write = Instant::now();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::shutdown::ShutdownHandle;
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    #[test]
+    fn formats_every_field() {
+        let event = Event::new("hello")
+            .id("7")
+            .event("greeting")
+            .retry(Duration::from_secs(3));
+        assert_eq!(
+            event.format(),
+            "id: 7\nevent: greeting\nretry: 3000\ndata: hello\n\n"
+        );
+    }
+
+    #[test]
+    fn splits_data_on_every_line_ending() {
+        let event = Event::new("a\nb\rc\r\nd");
+        assert_eq!(event.format(), "data: a\ndata: b\ndata: c\ndata: d\n\n");
+        // An ending line break is kept as an empty last line, as the client removes only one.
+        let event = Event::new("a\r\n");
+        assert_eq!(event.format(), "data: a\ndata: \n\n");
+        let event = Event::new("a\r\r\nb");
+        assert_eq!(event.format(), "data: a\ndata: \ndata: b\n\n");
+    }
+
+    #[test]
+    fn sends_empty_data_as_one_empty_line() {
+        assert_eq!(Event::new("").format(), "data: \n\n");
+    }
+
+    #[test]
+    fn removes_line_breaks_from_the_id_and_type() {
+        let event = Event::new("x").id("1\r\ndata: evil").event("a\rb\nc");
+        assert_eq!(event.format(), "id: 1data: evil\nevent: abc\ndata: x\n\n");
+    }
+
+    // Write events to a connected socket until write_events returns, and get everything the client received.
+    fn write_to_client(
+        shutdown: ShutdownHandle,
+        send: impl FnOnce(EventSender) + Send + 'static,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let (sender, receiver) = event_stream();
+        thread::spawn(move || send(sender));
+        let mut writer = ChunkedWriter::new(&stream, false, Some(shutdown));
+        write_events(receiver, &mut writer).unwrap();
+        writer.finish().unwrap();
+        drop(stream);
+        let mut received = String::new();
+        client.read_to_string(&mut received).unwrap();
+        received
+    }
+
+    #[test]
+    fn writes_events_until_every_sender_is_dropped() {
+        let received = write_to_client(ShutdownHandle::new(), |sender| {
+            assert!(sender.send(Event::new("1")));
+            assert!(sender.clone().send(Event::new("2")));
+        });
+        assert_eq!(received, "data: 1\n\ndata: 2\n\n");
+    }
+
+    #[test]
+    fn ends_the_stream_when_the_server_shuts_down() {
+        let shutdown = ShutdownHandle::new();
+        let handle = shutdown.clone();
+        let received = write_to_client(shutdown, move |sender| {
+            assert!(sender.send(Event::new("1")));
+            thread::sleep(Duration::from_millis(50));
+            handle.shutdown();
+            // The sender is kept alive, the stream ends because of the shutdown.
+            thread::sleep(Duration::from_secs(5));
+            drop(sender);
+        });
+        assert_eq!(received, "data: 1\n\n");
+    }
+}
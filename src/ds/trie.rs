@@ -1,7 +1,17 @@
 use std::collections::HashMap;
 
+use crate::utils::url::percent_decode;
+
+// Values captured by parameters and wildcards, as (name, value) pairs.
+type Params = Vec<(String, String)>;
+
+// Trie of URL paths, where every node is a path segment ("/users/:id" is "users" -> ":id").
+// Segments starting with ":" are parameters matching any single segment, and a final segment starting with "*" is a wildcard matching the rest of the path.
+// Searched paths are percent-decoded segment by segment, so an encoded "/" ("%2F") is part of a segment instead of separating segments.
 struct Node<T> {
-    children: HashMap<char, Node<T>>,
+    children: HashMap<String, Node<T>>,
+    param: Option<(String, Box<Node<T>>)>,
+    wildcard: Option<(String, T)>,
     is_end: bool,
     value: Option<T>,
 }
@@ -13,16 +23,97 @@ where
     fn new() -> Self {
         Self {
             children: HashMap::new(),
+            param: None,
+            wildcard: None,
             is_end: false,
             value: None,
         }
     }
+
+    // Find the value matching the given segments. Exact segments are preferred over parameters, and parameters over wildcards.
+    fn find(&self, segments: &[&str], params: &mut Params) -> Option<&T> {
+        let Some((segment, rest)) = segments.split_first() else {
+            if self.is_end {
+                return self.value.as_ref();
+            }
+            return self.wildcard.as_ref().map(|(name, value)| {
+                params.push((name.clone(), String::new()));
+                value
+            });
+        };
+        if let Some(value) = self
+            .children
+            .get(*segment)
+            .and_then(|child| child.find(rest, params))
+        {
+            return Some(value);
+        }
+        if let Some((name, child)) = &self.param {
+            params.push((name.clone(), segment.to_string()));
+            if let Some(value) = child.find(rest, params) {
+                return Some(value);
+            }
+            params.pop();
+        }
+        self.wildcard.as_ref().map(|(name, value)| {
+            params.push((name.clone(), segments.join("/")));
+            value
+        })
+    }
+
+    // Find the value whose path is the longest prefix of the given segments.
+    // Returns the value with the amount of segments it matched and the values captured by parameters and wildcards.
+    fn find_prefix(&self, segments: &[&str]) -> Option<(usize, &T, Params)> {
+        let mut longest = match self.is_end {
+            true => self.value.as_ref().map(|value| (0, value, Vec::new())),
+            false => None,
+        };
+        let mut candidates = Vec::new();
+        if let Some((segment, rest)) = segments.split_first() {
+            if let Some(child) = self.children.get(*segment) {
+                candidates.push(
+                    child
+                        .find_prefix(rest)
+                        .map(|(depth, value, params)| (depth + 1, value, params)),
+                );
+            }
+            if let Some((name, child)) = &self.param {
+                candidates.push(child.find_prefix(rest).map(|(depth, value, mut params)| {
+                    params.push((name.clone(), segment.to_string()));
+                    (depth + 1, value, params)
+                }));
+            }
+        }
+        if let Some((name, value)) = &self.wildcard {
+            let params = vec![(name.clone(), segments.join("/"))];
+            candidates.push(Some((segments.len(), value, params)));
+        }
+        // Earlier candidates win ties, so exact segments are preferred over parameters and wildcards.
+        for candidate in candidates.into_iter().flatten() {
+            if longest
+                .as_ref()
+                .is_none_or(|(longest_depth, _, _)| candidate.0 > *longest_depth)
+            {
+                longest = Some(candidate);
+            }
+        }
+        longest
+    }
 }
 
 pub struct Trie<T> {
     root: Node<T>,
 }
 
+impl<T> Default for Trie<T>
+where
+    T: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> Trie<T>
 where
     T: Clone,
@@ -33,25 +124,200 @@ where
 
     pub fn insert(&mut self, word: &str, value: T) {
         let mut node = &mut self.root;
-        for c in word.chars() {
-            node = node.children.entry(c).or_insert(Node::new());
+        for segment in Self::segments(word) {
+            if let Some(name) = segment.strip_prefix('*') {
+                node.wildcard = Some((name.to_string(), value));
+                return;
+            }
+            node = match segment.strip_prefix(':') {
+                Some(name) => {
+                    let (param_name, child) = node
+                        .param
+                        .get_or_insert_with(|| (name.to_string(), Box::new(Node::new())));
+                    assert!(
+                        param_name == name,
+                        "Conflicting parameter names :{} and :{} in path: {}",
+                        param_name,
+                        name,
+                        word
+                    );
+                    child
+                }
+                None => node
+                    .children
+                    .entry(segment.to_string())
+                    .or_insert(Node::new()),
+            };
         }
         node.is_end = true;
         node.value = Some(value);
     }
 
     pub fn search(&self, word: &str) -> Option<T> {
-        let mut node = &self.root;
-        for c in word.chars() {
-            if let Some(child) = node.children.get(&c) {
-                node = child;
-            } else {
-                return None;
+        self.search_params(word).map(|(value, _)| value)
+    }
+
+    // Search for the value matching the path, also returning the values captured by parameters and wildcards.
+    pub fn search_params(&self, word: &str) -> Option<(T, HashMap<String, String>)> {
+        let decoded = Self::decoded_segments(word);
+        let segments = decoded.iter().map(String::as_str).collect::<Vec<&str>>();
+        let mut params = Vec::new();
+        self.root
+            .find(&segments, &mut params)
+            .map(|value| (value.clone(), params.into_iter().collect()))
+    }
+
+    // Search for the value whose path is the longest prefix of the given path ("/api" for "/api/users").
+    // Also returns the values captured by parameters and wildcards, and the rest of the path not matched by the prefix ("/users"), still percent-encoded.
+    pub fn search_prefix(&self, word: &str) -> Option<(T, HashMap<String, String>, String)> {
+        let segments = Self::segments(word).collect::<Vec<&str>>();
+        let decoded = Self::decoded_segments(word);
+        let decoded = decoded.iter().map(String::as_str).collect::<Vec<&str>>();
+        self.root
+            .find_prefix(&decoded)
+            .map(|(depth, value, params)| {
+                let rest = format!("/{}", segments[depth..].join("/"));
+                (value.clone(), params.into_iter().collect(), rest)
+            })
+    }
+
+    // Get a mutable reference to the value inserted with exactly the given path (parameters and wildcards are not matched).
+    pub fn get_mut(&mut self, word: &str) -> Option<&mut T> {
+        let mut node = &mut self.root;
+        for segment in Self::segments(word) {
+            if segment.starts_with('*') {
+                return node.wildcard.as_mut().map(|(_, value)| value);
             }
+            node = match segment.strip_prefix(':') {
+                Some(name) => match &mut node.param {
+                    Some((param_name, child)) if param_name == name => child,
+                    _ => return None,
+                },
+                None => node.children.get_mut(segment)?,
+            };
         }
-        if !node.is_end {
-            return None;
+        node.value.as_mut()
+    }
+
+    fn segments(word: &str) -> impl Iterator<Item = &str> {
+        word.split('/').filter(|segment| !segment.is_empty())
+    }
+
+    fn decoded_segments(word: &str) -> Vec<String> {
+        Self::segments(word)
+            .map(|segment| percent_decode(segment, false))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trie(paths: &[&'static str]) -> Trie<&'static str> {
+        let mut trie = Trie::new();
+        for path in paths {
+            trie.insert(path, *path);
         }
-        Some(node.value.clone().unwrap())
+        trie
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn prefix_prefers_the_longest_match() {
+        let trie = trie(&["/api", "/api/users"]);
+        let (value, _, rest) = trie.search_prefix("/api/users/1").unwrap();
+        assert_eq!((value, rest.as_str()), ("/api/users", "/1"));
+        let (value, _, rest) = trie.search_prefix("/api/posts").unwrap();
+        assert_eq!((value, rest.as_str()), ("/api", "/posts"));
+    }
+
+    #[test]
+    fn prefix_matches_whole_segments_only() {
+        let trie = trie(&["/api"]);
+        assert!(trie.search_prefix("/apiary").is_none());
+        let (value, _, rest) = trie.search_prefix("/api").unwrap();
+        assert_eq!((value, rest.as_str()), ("/api", "/"));
+    }
+
+    #[test]
+    fn prefix_root_matches_everything() {
+        let trie = trie(&["", "/api"]);
+        let (value, _, rest) = trie.search_prefix("/other/path").unwrap();
+        assert_eq!((value, rest.as_str()), ("", "/other/path"));
+    }
+
+    #[test]
+    fn prefix_tie_prefers_exact_segment_over_param() {
+        let trie = trie(&["/users/:id", "/users/me"]);
+        let (value, captured, rest) = trie.search_prefix("/users/me/posts").unwrap();
+        assert_eq!((value, rest.as_str()), ("/users/me", "/posts"));
+        assert!(captured.is_empty());
+        let (value, captured, _) = trie.search_prefix("/users/7/posts").unwrap();
+        assert_eq!(value, "/users/:id");
+        assert_eq!(captured, params(&[("id", "7")]));
+    }
+
+    #[test]
+    fn prefix_deeper_param_beats_shallower_exact_segment() {
+        let trie = trie(&["/users/me", "/users/:id/posts"]);
+        let (value, captured, rest) = trie.search_prefix("/users/me/posts/1").unwrap();
+        assert_eq!((value, rest.as_str()), ("/users/:id/posts", "/1"));
+        assert_eq!(captured, params(&[("id", "me")]));
+    }
+
+    #[test]
+    fn prefix_tie_prefers_param_over_wildcard() {
+        let trie = trie(&["/files/:name", "/files/*path"]);
+        let (value, captured, _) = trie.search_prefix("/files/a").unwrap();
+        assert_eq!(value, "/files/:name");
+        assert_eq!(captured, params(&[("name", "a")]));
+    }
+
+    #[test]
+    fn prefix_wildcard_beats_param_when_it_matches_more() {
+        let trie = trie(&["/files/:name", "/files/*path"]);
+        let (value, captured, rest) = trie.search_prefix("/files/a/b").unwrap();
+        assert_eq!((value, rest.as_str()), ("/files/*path", "/"));
+        assert_eq!(captured, params(&[("path", "a/b")]));
+    }
+
+    #[test]
+    fn prefix_wildcard_matches_an_empty_rest() {
+        let trie = trie(&["/static/*path"]);
+        let (value, captured, rest) = trie.search_prefix("/static").unwrap();
+        assert_eq!((value, rest.as_str()), ("/static/*path", "/"));
+        assert_eq!(captured, params(&[("path", "")]));
+    }
+
+    #[test]
+    fn prefix_exact_value_beats_wildcard_at_the_same_node() {
+        let trie = trie(&["/static", "/static/*path"]);
+        let (value, captured, _) = trie.search_prefix("/static").unwrap();
+        assert_eq!(value, "/static");
+        assert!(captured.is_empty());
+    }
+
+    #[test]
+    fn prefix_decodes_segments_but_keeps_the_rest_encoded() {
+        let trie = trie(&["/files/:name"]);
+        let (_, captured, rest) = trie.search_prefix("/files/a%2Fb/c%20d").unwrap();
+        assert_eq!(captured, params(&[("name", "a/b")]));
+        assert_eq!(rest, "/c%20d");
+    }
+
+    #[test]
+    fn search_captures_params_and_wildcards() {
+        let trie = trie(&["/users/:id/files/*path"]);
+        let (value, captured) = trie.search_params("/users/7/files/a/b%20c").unwrap();
+        assert_eq!(value, "/users/:id/files/*path");
+        assert_eq!(captured, params(&[("id", "7"), ("path", "a/b c")]));
+        assert!(trie.search("/users/7").is_none());
     }
 }
@@ -1,21 +1,18 @@
 use std::{
     env,
     fs::File,
-    io::Write,
-    sync::{Mutex, Once},
+    io::{self, Write},
+    sync::{Mutex, OnceLock},
     time::SystemTime,
 };
 
-static INIT_LOGGER: Once = Once::new();
-pub static mut LOGGER: Option<Mutex<Logger>> = None;
+pub static LOGGER: OnceLock<Mutex<Logger>> = OnceLock::new();
 
 #[macro_export]
 macro_rules! log {
     ($($arg:tt)*) => {
-        unsafe {
-            if let Some(logger) = &crate::log::logger::LOGGER {
-                logger.lock().unwrap().log(&format_args!($($arg)*).to_string());
-           }
+        if let Some(logger) = $crate::log::logger::LOGGER.get() {
+            logger.lock().unwrap().log(&format_args!($($arg)*).to_string());
         }
     };
 }
@@ -27,17 +24,20 @@ pub struct Logger {
 }
 
 impl Logger {
-    pub fn init(log_file_path: &str) {
-        INIT_LOGGER.call_once(|| {
-            let mut path = env::current_dir().unwrap();
-            path.push(log_file_path);
-            unsafe {
-                LOGGER = Some(Mutex::new(Logger {
-                    log_file: File::create(path).unwrap(),
-                    start_time: SystemTime::now(),
-                }))
-            };
-        });
+    // Create the log file (relative to the current directory or absolute) and start logging to it. Does nothing if the logger is already initialized.
+    pub fn init(log_file_path: &str) -> io::Result<()> {
+        if LOGGER.get().is_some() {
+            return Ok(());
+        }
+        let mut path = env::current_dir()?;
+        path.push(log_file_path);
+        let log_file = File::create(path)?;
+        // Another thread may have initialized the logger meanwhile, its logger is then kept.
+        let _ = LOGGER.set(Mutex::new(Logger {
+            log_file,
+            start_time: SystemTime::now(),
+        }));
+        Ok(())
     }
 
     // Get the time since the logger was initialized.
@@ -50,10 +50,11 @@ impl Logger {
 
     // Log a message to the log file.
     pub fn log(&mut self, message: &str) {
-        if let Err(e) = write!(
+        if let Err(e) = writeln!(
             self.log_file,
-            "{}",
-            format!("{}: {} \n", self.get_time_since_start(), message)
+            "{}: {} ",
+            self.get_time_since_start(),
+            message
         ) {
             println!("LOG ERROR: {e}");
         }
@@ -1,26 +1,87 @@
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
 use tiny_rust_server::communication::router::Router;
+use tiny_rust_server::communication::sse::Event;
 use tiny_rust_server::server::Server;
 
 fn main() {
-    // Create a new server instance at localhost, port 5000
-    match Server::new((127, 0, 0, 1), 5000) {
+    // Create a new server instance at localhost, port 5000, configured with the builder
+    // It stops gracefully on Ctrl+C, a ShutdownHandle from server.shutdown_handle() can also be used to stop the server
+    match Server::builder("127.0.0.1:5000")
+        .shutdown_on_signal()
+        .build()
+    {
         Ok(mut server) => {
             // Serve static files from the "public" folder at project root
             server.serve_static("public");
 
             // Define a new router instance
             let mut router = Router::new("/test");
-            // Add a middleware to the router (executed before every request)
-            router.middleware(|_mid, req| {
+            // Add a middleware to the router (wraps every request of the router)
+            router.middleware(|req, res, next| {
                 println!("MIDDLEWARE executed");
-                req.headers
-                    .insert("User-Agent".to_string(), "Testi".to_string());
+                req.headers.insert("User-Agent", "Testi");
+                // Call the rest of the chain (other middleware and the route), or respond here to stop the request
+                next.run(req, res);
+                res.insert_header("X-Powered-By", "tiny-rust-server");
             });
             // Add a route to the router, route will be router base path + router path
             router.route("", "GET", |req, res| {
                 println!("GET \nRequest: {:#?} \nResponse: {:#?}", req, res);
+                res.insert_header("Cache-Control", "no-cache");
                 res.set_content("Hello");
+                Ok(())
+            });
+            // Routes can capture path parameters (":name") and the rest of the path with a trailing wildcard ("*name")
+            // Route functions return a Result, errors (e.g. a failed parse here) are turned into error responses
+            router.route("/users/:id/files/*path", "GET", |req, res| {
+                let id = req.params["id"].parse::<u32>()?;
+                res.set_text(&format!("User {} file {}", id, req.params["path"]));
+                Ok(())
+            });
+            // Content can also be written over time, it is sent in chunks (Transfer-Encoding: chunked) as it is written
+            router.route("/export", "GET", |_req, res| {
+                res.insert_header("Trailer", "X-Row-Count");
+                res.set_chunked("text/csv", |writer| {
+                    for row in 0..3 {
+                        writeln!(writer, "{},row {}", row, row)?;
+                    }
+                    writer.trailer("X-Row-Count", "3");
+                    Ok(())
+                });
+                Ok(())
+            });
+            // Events can be pushed to the browser (Server-Sent Events) from another thread while the stream is open
+            router.route("/events", "GET", |_req, res| {
+                let events = res.set_event_stream();
+                thread::spawn(move || {
+                    for tick in 0.. {
+                        let event = Event::new(&format!("tick {}", tick)).event("tick");
+                        // Stop when the client has disconnected
+                        if !events.send(event.id(&tick.to_string())) {
+                            break;
+                        }
+                        thread::sleep(Duration::from_secs(1));
+                    }
+                });
+                Ok(())
+            });
+            // Routers can be mounted under other routers, the mounted router handles "/test/v1/admin" and everything below it
+            let mut admin = Router::new("/admin");
+            admin.route("", "GET", |_req, res| {
+                res.set_text("Admin");
+                Ok(())
+            });
+            // Middleware can also be attached to a single route (or a single method of a route)
+            admin.method_middleware("", "GET", |req, res, next| {
+                match req.headers.get("Authorization") {
+                    Some(_) => next.run(req, res),
+                    None => res.set_status(401, "Unauthorized"),
+                }
             });
+            router.mount("/v1", admin);
             // Finally, register router with the server
             server.router(router);
 
@@ -1,82 +1,122 @@
-use crate::communication::request::{Request, StaticRequestData};
+pub mod builder;
+pub mod config;
+pub mod shutdown;
+pub mod static_files;
+
+use crate::communication::error::HttpError;
+use crate::communication::request::Request;
 
 use crate::communication::response::Response;
+use crate::communication::route::Fallbacks;
 use crate::communication::router::Router;
 use crate::ds::trie::Trie;
-use crate::log;
-use crate::log::logger::Logger;
-use crate::utils::file::get_first_html_file_name;
-use crate::utils::general::is_static_file;
-use crate::utils::guess::guess_mime_type;
-use crate::utils::thread_pool::ThreadPool;
-
-use std::env::current_dir;
+use crate::server::builder::ServerBuilder;
+use crate::server::config::ServerConfig;
+use crate::server::shutdown::ShutdownHandle;
+use crate::server::static_files::StaticFiles;
+use crate::utils::general::get_panic_message;
+use crate::utils::signal::{is_termination_received, listen_for_termination};
+use crate::utils::thread_pool::{QueuedJobs, ThreadPool};
+
 use std::error::Error;
-use std::fmt::{self, Display, Formatter};
-use std::fs::read_to_string;
-use std::net::{TcpListener, TcpStream};
+use std::io::{self, BufRead, BufReader, ErrorKind};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often an idle connection checks if it should be given up, see Server::wait_for_request.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+// How often a received termination signal is checked for, see Server::shutdown_on_signal.
+const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 // This is the main entry point for the server.
 pub struct Server {
     thread_pool: ThreadPool,
     listener: TcpListener,
     routers: Arc<Mutex<Trie<Router>>>,
-    _address: String,
-    root_path: String,
+    fallbacks: Fallbacks,
+    config: ServerConfig,
+    shutdown: ShutdownHandle,
+    static_files: Vec<StaticFiles>,
 }
 
-// Represents the server address, which is a tuple of an IP address and a port.
-struct Address {
-    ip: (u8, u8, u8, u8),
-    port: u16,
-}
+impl Server {
+    // Create a server listening on the IPv4 address and port with the default settings. Use ServerBuilder for other settings.
+    pub fn new(ip: (u8, u8, u8, u8), port: u16) -> Result<Server, Box<dyn Error>> {
+        let address = SocketAddr::from((Ipv4Addr::new(ip.0, ip.1, ip.2, ip.3), port));
+        ServerBuilder::new(address).build()
+    }
 
-impl Display for Address {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}.{}.{}.{}:{}",
-            self.ip.0, self.ip.1, self.ip.2, self.ip.3, self.port
-        )
+    pub fn builder(address: impl ToSocketAddrs) -> ServerBuilder {
+        ServerBuilder::new(address)
     }
-}
 
-impl Server {
-    pub fn new(ip: (u8, u8, u8, u8), port: u16) -> Result<Server, Box<dyn Error>> {
-        let _address: String = Address { ip, port }.to_string();
-        match TcpListener::bind(&_address) {
-            Ok(listener) => {
-                Logger::init("log.txt");
-                log!("Server listening on: {}", _address);
-                return Ok(Server {
-                    thread_pool: ThreadPool::new(5),
-                    listener,
-                    routers: Arc::new(Mutex::new(Trie::new())),
-                    _address,
-                    root_path: current_dir().unwrap_or_default().display().to_string(),
-                });
-            }
-            Err(e) => {
-                log!("Listener Error: {:#?}", e);
-                return Err(Box::new(e));
-            }
-        };
+    pub(crate) fn from_listener(
+        listener: TcpListener,
+        workers: usize,
+        config: ServerConfig,
+    ) -> Self {
+        let shutdown = ShutdownHandle::new().waking(listener.local_addr().ok());
+        Server {
+            thread_pool: ThreadPool::new(workers),
+            listener,
+            routers: Arc::new(Mutex::new(Trie::new())),
+            fallbacks: Fallbacks::default(),
+            config,
+            shutdown,
+            static_files: Vec::new(),
+        }
+    }
+
+    // Get the address the server is listening on, e.g. to find out the port chosen by the OS when binding to port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
     }
 
+    // Accept connections until shutdown is requested (see shutdown_handle), then wait for in-flight requests to finish and return.
     pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
-        for stream in self.listener.incoming() {
-            let routers = self.routers.clone();
-            match stream {
-                Ok(stream) => {
-                    self.thread_pool
-                        .execute(move || match Request::build_request(&stream) {
-                            Ok(mut request) => {
-                                Self::handle_loop(&routers, &stream, &mut request);
-                            }
-                            Err(e) => log!("Request Error: {:#?}", e),
-                        });
+        if self.config.shutdown_on_signal {
+            match listen_for_termination() {
+                true => Self::watch_for_termination(self.shutdown.clone()),
+                false => log!("Signal Error: Shutdown on signal is not supported on this platform"),
+            }
+        }
+        // Accept blocks until the next connection, a shutdown request wakes it up by connecting to the listener.
+        while !self.shutdown.is_shutdown() {
+            match self.listener.accept() {
+                Ok(_) if self.shutdown.is_shutdown() => break,
+                Ok((stream, _)) => {
+                    let routers = self.routers.clone();
+                    let fallbacks = self.fallbacks.clone();
+                    let config = self.config.clone();
+                    let static_files = self.static_files.clone();
+                    let shutdown = self.shutdown.clone();
+                    let queued_jobs = self.thread_pool.queued_jobs();
+                    self.thread_pool.execute(move || {
+                        Self::handle_connection(
+                            &routers,
+                            &fallbacks,
+                            &static_files,
+                            &config,
+                            &shutdown,
+                            &queued_jobs,
+                            &stream,
+                        )
+                    });
+                }
+                // Errors of a single connection (e.g. the client gave up before it was accepted) do not stop the server.
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::ConnectionAborted
+                            | ErrorKind::ConnectionReset
+                            | ErrorKind::Interrupted
+                    ) =>
+                {
+                    log!("Stream Error: {:#?}", e);
                 }
                 Err(e) => {
                     log!("Stream Error: {:#?}", e);
@@ -84,62 +124,244 @@ impl Server {
                 }
             }
         }
+        log!("Server shutting down");
+        if !self.thread_pool.shutdown(self.config.shutdown_timeout) {
+            log!("Shutdown Error: Requests still in progress after the shutdown timeout");
+        }
         Ok(())
     }
 
-    // Execute main request-response "loop" logic for the server.
-    fn handle_loop(routers: &Arc<Mutex<Trie<Router>>>, stream: &TcpStream, request: &mut Request) {
-        Self::check_static_request(request);
+    // Shut down the server once SIGINT or SIGTERM is received. The signal handler can only set a flag, so the flag is checked by a separate thread.
+    fn watch_for_termination(shutdown: ShutdownHandle) {
+        thread::spawn(move || {
+            while !shutdown.is_shutdown() {
+                if is_termination_received() {
+                    shutdown.shutdown();
+                }
+                thread::sleep(SIGNAL_POLL_INTERVAL);
+            }
+        });
+    }
+
+    // Get a handle that can be used to shut down the server from another thread while it is running.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    // Handle the requests of a connection one after another, until the client or the server closes the connection.
+    // Pipelined requests are read from the same buffered reader, so their responses are sent in order.
+    fn handle_connection(
+        routers: &Arc<Mutex<Trie<Router>>>,
+        fallbacks: &Fallbacks,
+        static_files: &[StaticFiles],
+        config: &ServerConfig,
+        shutdown: &ShutdownHandle,
+        queued_jobs: &QueuedJobs,
+        stream: &TcpStream,
+    ) {
+        if let Err(e) = stream.set_write_timeout(config.write_timeout) {
+            log!("Stream Error: {:#?}", e);
+            return;
+        }
+        let mut reader = BufReader::new(stream);
+        let max_requests = config.keep_alive.max_requests.max(1);
+        for count in 1..=max_requests {
+            let idle = count > 1;
+            if !Self::wait_for_request(&mut reader, config, shutdown, queued_jobs, idle) {
+                break;
+            }
+            match Request::build_request(&mut reader, &config.limits) {
+                Ok(mut request) => {
+                    // Connections are closed after the current request when the server is shutting down.
+                    let keep_open =
+                        request.is_keep_alive() && count < max_requests && !shutdown.is_shutdown();
+                    if !Self::handle_loop(
+                        routers,
+                        fallbacks,
+                        static_files,
+                        shutdown,
+                        stream,
+                        &mut request,
+                        keep_open,
+                    ) {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    log!("Request Error: {:#?}", e);
+                    Self::reject_request(stream, e.as_ref());
+                    break;
+                }
+            }
+        }
+    }
+
+    // Wait until the next request starts arriving, at most for the keep-alive timeout. Returns false if the connection was closed or timed out.
+    // An idle connection (between requests) is given up early when other connections are waiting for a worker or the server is shutting down, so it does not keep them waiting.
+    fn wait_for_request(
+        reader: &mut BufReader<&TcpStream>,
+        config: &ServerConfig,
+        shutdown: &ShutdownHandle,
+        queued_jobs: &QueuedJobs,
+        idle: bool,
+    ) -> bool {
+        let stream = *reader.get_ref();
+        let timeout = config.keep_alive.timeout;
+        let waiting_since = Instant::now();
+        let started = loop {
+            let remaining = match timeout.is_zero() {
+                true => IDLE_POLL_INTERVAL,
+                false => timeout.saturating_sub(waiting_since.elapsed()),
+            };
+            if remaining.is_zero() {
+                break Ok(false);
+            }
+            if let Err(e) = stream.set_read_timeout(Some(remaining.min(IDLE_POLL_INTERVAL))) {
+                break Err(e);
+            }
+            // Pipelined requests are already buffered, so they are returned without waiting.
+            match reader.fill_buf() {
+                Ok(buffer) => break Ok(!buffer.is_empty()),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    if idle && (queued_jobs.get() > 0 || shutdown.is_shutdown()) {
+                        break Ok(false);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        let started = started.and_then(|started| {
+            stream.set_read_timeout(config.read_timeout.filter(|timeout| !timeout.is_zero()))?;
+            Ok(started)
+        });
+        started.unwrap_or(false)
+    }
+
+    // Execute main request-response "loop" logic for the server. Returns true if the connection should be kept open for the next request.
+    fn handle_loop(
+        routers: &Arc<Mutex<Trie<Router>>>,
+        fallbacks: &Fallbacks,
+        static_files: &[StaticFiles],
+        shutdown: &ShutdownHandle,
+        stream: &TcpStream,
+        request: &mut Request,
+        keep_open: bool,
+    ) -> bool {
         let mut response = Response::new();
-        Self::match_router(routers, request, &mut response);
-        log!("Request: {:#?}", request);
-        if let Err(e) = response.send(stream) {
+        // A panicking route function responds with 500 Internal Server Error instead of taking down the worker.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            if !Self::serve_static_request(static_files, fallbacks, request, &mut response) {
+                Self::match_router(routers, fallbacks, request, &mut response)
+            }
+        }));
+        if let Err(payload) = result {
+            log!("Route Panic: {}", get_panic_message(payload.as_ref()));
+            response = Response::new();
+            response.set_status(500, "Internal Server Error");
+            response.set_text("Internal Server Error");
+        }
+        // The body is logged by its length only, as it can be up to the maximum body size.
+        log!(
+            "Request: {} {} {} {:#?} (body: {} bytes)",
+            request.method.as_str(),
+            request.path,
+            request.version,
+            request.headers,
+            request.body.len()
+        );
+        // Clients before HTTP/1.1 do not support chunked responses, so chunked content is ended by closing the connection instead.
+        let supports_chunked = request.version == "HTTP/1.1";
+        // A route function can close the connection by setting "Connection: close".
+        let keep_open = keep_open
+            && (supports_chunked || !response.is_chunked())
+            && !response
+                .headers
+                .get("Connection")
+                .is_some_and(|value| value.eq_ignore_ascii_case("close"));
+        response.insert_header("Connection", if keep_open { "keep-alive" } else { "close" });
+        let sent = response.send_from_server(stream, supports_chunked, shutdown);
+        if let Err(e) = sent {
             log!("Response Error: {:#?}", e);
+            return false;
         }
+        keep_open
     }
 
-    // Check if the request is for a static file, and add the static request data to the request object if so. Also change to forward to the static route.
-    fn check_static_request(request: &mut Request) {
-        if request.path == "/" {
-            request.static_request_data = Some(StaticRequestData { path: None });
-            request.path = String::from("/static");
-        } else {
-            let static_info = Self::is_static_path(request);
-            if static_info.0 {
-                request.static_request_data = Some(StaticRequestData {
-                    path: Some(static_info.1),
-                });
-                request.path = String::from("/static");
-            }
+    // Respond to a request that could not be parsed. Connection level errors are not responded to.
+    fn reject_request(stream: &TcpStream, error: &(dyn Error + 'static)) {
+        let mut response = Response::new();
+        response.insert_header("Connection", "close");
+        match error.downcast_ref::<io::Error>().map(|e| e.kind()) {
+            Some(ErrorKind::InvalidData) => response.set_status(400, "Bad Request"),
+            Some(ErrorKind::FileTooLarge) => response.set_status(413, "Payload Too Large"),
+            Some(ErrorKind::Unsupported) => response.set_status(501, "Not Implemented"),
+            _ => return,
+        }
+        if let Err(e) = response.send(stream) {
+            log!("Response Error: {:#?}", e);
         }
     }
 
-    // Static method to check if the request path is a static file.
-    fn is_static_path(request: &Request) -> (bool, String) {
-        let mut is_static = false;
-        let path = Path::new(&request.path);
-        match path.extension() {
-            Some(extension) => {
-                is_static = is_static_file(&extension.to_string_lossy());
+    // Serve the request from the first static directory containing the requested path. Returns false if no static directory handled the request.
+    fn serve_static_request(
+        static_files: &[StaticFiles],
+        fallbacks: &Fallbacks,
+        request: &Request,
+        response: &mut Response,
+    ) -> bool {
+        for files in static_files {
+            match files.serve(request, response) {
+                Ok(true) => return true,
+                Ok(false) => continue,
+                Err(error) => {
+                    fallbacks.error(&error, request, response);
+                    return true;
+                }
             }
-            None => {}
         }
-        (is_static, path.to_string_lossy().to_string())
+        false
     }
 
-    // Static method to match the request to the correct route, and then call the possible user registered function found on that route.
+    // Static method to match the request to the most specific router mounted under the request path, and then let the router handle the rest of the path.
     fn match_router(
         routers: &Arc<Mutex<Trie<Router>>>,
+        fallbacks: &Fallbacks,
         request: &mut Request,
         response: &mut Response,
     ) {
-        routers.lock().unwrap().search(&request.path).map(|router| {
-            router.execute_middleware(request);
-            router
-                .find_route(request)
-                .and_then(|route| route.method_map.get(&request.method).cloned())
-                .map(|func| (func)(request, response));
-        });
+        let router = routers.lock().unwrap().search_prefix(&request.path);
+        match router {
+            Some((router, params, path)) => {
+                request.params.extend(params);
+                router.handle(&path, fallbacks, request, response);
+            }
+            None => fallbacks.not_found(request, response),
+        }
+    }
+
+    // Register a function called when no router or route matches the request path.
+    pub fn not_found<F>(&mut self, func: F)
+    where
+        F: Fn(&Request, &mut Response) -> Result<(), HttpError> + Send + Sync + 'static,
+    {
+        self.fallbacks.not_found = Some(Arc::new(func));
+    }
+
+    // Register a function called when a route matches the request path, but not the request method.
+    pub fn method_not_allowed<F>(&mut self, func: F)
+    where
+        F: Fn(&Request, &mut Response) -> Result<(), HttpError> + Send + Sync + 'static,
+    {
+        self.fallbacks.method_not_allowed = Some(Arc::new(func));
+    }
+
+    // Register a function that turns errors returned from route functions into responses. By default the response has the status code of the error, and the error message as content.
+    pub fn error_handler<F>(&mut self, func: F)
+    where
+        F: Fn(&HttpError, &Request, &mut Response) + Send + Sync + 'static,
+    {
+        self.fallbacks.error = Some(Arc::new(func));
     }
 
     // Register a router with the server. Routers are used to group routes together.
@@ -150,46 +372,15 @@ impl Server {
             .insert(router.base_path.clone().as_str(), router);
     }
 
-    // Register a route with the server that serves static files from a directory starting from project root.
-    pub fn serve_static(&mut self, dir: &str) {
-        let root_path = format!("{}\\{}", self.root_path, dir);
-        let mut router = Router::new("/static");
-        router.route("", "GET", move |request, response| {
-            if let Some((path, extension)) = Self::get_static_file_details(request, &root_path) {
-                match read_to_string(path) {
-                    Ok(file_content) => {
-                        response.set_contents(&guess_mime_type(&extension), &file_content)
-                    }
-                    Err(e) => {
-                        response.set_status(404, "Not Found");
-                        log!("File Read Error: {:#?}", e);
-                    }
-                }
-            }
-        });
-        self.router(router);
-    }
-
-    fn get_static_file_details(request: &Request, root_path: &str) -> Option<(String, String)> {
-        if let Some(ref data) = request.static_request_data {
-            // If the request has a path, use that path to get the file. Otherwise, get the first HTML file in the directory.
-            if let Some(ref path) = data.path {
-                return Some((
-                    format!("{}\\{}", root_path, path),
-                    path.split('.').last().unwrap_or("text/plain").to_string(),
-                ));
-            } else {
-                match get_first_html_file_name(Path::new(&root_path)) {
-                    Ok((resource, extension)) => {
-                        return Some((format!("{}\\{}", root_path, resource), extension));
-                    }
-                    Err(e) => {
-                        log!("Static File Retrieval Error (No HTML File Found): {:#?}", e);
-                        return None;
-                    }
-                }
-            }
-        }
-        None
+    // Register a route with the server that serves static files from a directory, an absolute path or relative to the root path of the server (the working directory by default).
+    // Static directories are checked before the routers, so a file or directory shadows a route with the same path. Keep routes and files apart, e.g. routes under "/api".
+    pub fn serve_static(&mut self, dir: impl AsRef<Path>) {
+        self.serve_static_files(StaticFiles::new(dir));
+    }
+
+    // Serve static files with the given settings, see StaticFiles. Directories are tried in registration order.
+    pub fn serve_static_files(&mut self, files: StaticFiles) {
+        self.static_files
+            .push(files.with_root(&self.config.root_path));
     }
 }
@@ -0,0 +1,162 @@
+use std::error::Error;
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::log::logger::Logger;
+use crate::server::config::{KeepAlive, ServerConfig};
+use crate::server::Server;
+
+// Builder for a server with custom settings.
+//
+//     let server = ServerBuilder::new("[::1]:0").workers(8).max_body_size(1024).build()?;
+//     println!("Listening on {}", server.local_addr()?);
+pub struct ServerBuilder {
+    addresses: io::Result<Vec<SocketAddr>>,
+    workers: usize,
+    log_file: Option<String>,
+    config: ServerConfig,
+}
+
+impl ServerBuilder {
+    // Create a builder for a server bound to the address. Any address accepted by TcpListener::bind is supported, e.g. "127.0.0.1:5000", "[::1]:8080" or port 0 for a port chosen by the OS.
+    pub fn new(address: impl ToSocketAddrs) -> Self {
+        Self {
+            addresses: address
+                .to_socket_addrs()
+                .map(|addresses| addresses.collect()),
+            workers: 5,
+            log_file: Some(String::from("log.txt")),
+            config: ServerConfig::default(),
+        }
+    }
+
+    // Set the amount of worker threads handling connections. Must be greater than 0, otherwise build fails.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    // Set the file the server logs to, relative to the current directory or absolute. The logger is shared by the whole process, so only the first server decides the file.
+    // Building fails if the file can not be created.
+    pub fn log_file(mut self, path: &str) -> Self {
+        self.log_file = Some(path.to_string());
+        self
+    }
+
+    // Disable logging to a file.
+    pub fn no_log(mut self) -> Self {
+        self.log_file = None;
+        self
+    }
+
+    // Set the maximum size of the request line and headers in bytes.
+    pub fn max_header_size(mut self, size: usize) -> Self {
+        self.config.limits.max_header_size = size;
+        self
+    }
+
+    // Set the maximum size of a request body in bytes.
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.config.limits.max_body_size = size;
+        self
+    }
+
+    // Set how long to wait for the rest of a request once it has started. None waits forever.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.read_timeout = timeout;
+        self
+    }
+
+    // Set how long to wait for the client to accept response data. None waits forever.
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.write_timeout = timeout;
+        self
+    }
+
+    // Configure persistent connections, see KeepAlive.
+    pub fn keep_alive(mut self, timeout: Duration, max_requests: usize) -> Self {
+        self.config.keep_alive = KeepAlive {
+            timeout,
+            max_requests,
+        };
+        self
+    }
+
+    // Set how long to wait for in-flight requests to finish when shutting down.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.config.shutdown_timeout = timeout;
+        self
+    }
+
+    // Shut down the server when the process receives SIGINT (Ctrl+C) or SIGTERM. Only supported on Unix platforms.
+    pub fn shutdown_on_signal(mut self) -> Self {
+        self.config.shutdown_on_signal = true;
+        self
+    }
+
+    // Set the directory static directories are served relative to (see Server::serve_static), the working directory by default.
+    // Relative paths are relative to the working directory.
+    pub fn root_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.config.root_path = path.as_ref().to_path_buf();
+        self
+    }
+
+    // Bind the listener and create the server.
+    pub fn build(self) -> Result<Server, Box<dyn Error>> {
+        if let Some(log_file) = &self.log_file {
+            Logger::init(log_file)?;
+        }
+        // The thread pool needs at least one worker to handle connections.
+        if self.workers == 0 {
+            let e = io::Error::new(
+                ErrorKind::InvalidInput,
+                "Worker count must be greater than 0",
+            );
+            log!("Builder Error: {:#?}", e);
+            return Err(Box::new(e));
+        }
+        let listener = self
+            .addresses
+            .and_then(|addresses| TcpListener::bind(addresses.as_slice()));
+        match listener {
+            Ok(listener) => {
+                log!("Server listening on: {:?}", listener.local_addr());
+                Ok(Server::from_listener(listener, self.workers, self.config))
+            }
+            Err(e) => {
+                log!("Listener Error: {:#?}", e);
+                Err(Box::new(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binds_to_an_ephemeral_port() {
+        let server = ServerBuilder::new("127.0.0.1:0").no_log().build().unwrap();
+        assert_ne!(server.local_addr().unwrap().port(), 0);
+    }
+
+    #[test]
+    fn fails_for_zero_workers() {
+        let result = ServerBuilder::new("127.0.0.1:0")
+            .workers(0)
+            .no_log()
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn fails_for_an_unwritable_log_file() {
+        let result = ServerBuilder::new("127.0.0.1:0")
+            .log_file("/nonexistent/tiny-rust-server/log.txt")
+            .build();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use std::env::current_dir;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::communication::request::RequestLimits;
+
+// Settings of a server, configured with ServerBuilder.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub keep_alive: KeepAlive,
+    pub limits: RequestLimits,
+    // How long to wait for the rest of a request once it has started. None waits forever.
+    pub read_timeout: Option<Duration>,
+    // How long to wait for the client to accept response data. None waits forever.
+    pub write_timeout: Option<Duration>,
+    // How long to wait for in-flight requests to finish when shutting down.
+    pub shutdown_timeout: Duration,
+    // Shut down on SIGINT (Ctrl+C) or SIGTERM. Only supported on Unix platforms.
+    pub shutdown_on_signal: bool,
+    // Directory static directories are served relative to, an absolute path or relative to the working directory.
+    pub root_path: PathBuf,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            keep_alive: KeepAlive::default(),
+            limits: RequestLimits::default(),
+            read_timeout: Some(Duration::from_secs(30)),
+            write_timeout: Some(Duration::from_secs(30)),
+            shutdown_timeout: Duration::from_secs(10),
+            shutdown_on_signal: false,
+            root_path: current_dir().unwrap_or_default(),
+        }
+    }
+}
+
+// Settings for persistent (keep-alive) connections. An open connection occupies a worker of the thread pool until it is closed or has been idle for the timeout.
+// An idle connection is closed early when other connections are waiting for a worker.
+#[derive(Debug, Clone, Copy)]
+pub struct KeepAlive {
+    // How long to wait for the next request on an idle connection. Zero disables the timeout.
+    pub timeout: Duration,
+    // How many requests are handled on one connection before it is closed. One disables keep-alive.
+    pub max_requests: usize,
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            max_requests: 100,
+        }
+    }
+}
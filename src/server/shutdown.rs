@@ -0,0 +1,85 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Handle to stop a running server from another thread. Obtained with Server::shutdown_handle before calling Server::run.
+// After shutdown is requested the server stops accepting connections, lets in-flight requests finish (up to the shutdown timeout) and returns from run.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    requested: Arc<AtomicBool>,
+    // Address of the listener of the server, connected to when shutting down to wake up the waiting accept.
+    wake_address: Option<SocketAddr>,
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownHandle {
+    pub fn new() -> Self {
+        Self {
+            requested: Arc::new(AtomicBool::new(false)),
+            wake_address: None,
+        }
+    }
+
+    // Wake up the server listening on the address when shutdown is requested.
+    pub(crate) fn waking(mut self, address: Option<SocketAddr>) -> Self {
+        // A listener bound to every interface ("0.0.0.0" or "[::]") is reached through the loopback interface.
+        self.wake_address = address.map(|mut address| {
+            match address.ip() {
+                IpAddr::V4(ip) if ip.is_unspecified() => address.set_ip(Ipv4Addr::LOCALHOST.into()),
+                IpAddr::V6(ip) if ip.is_unspecified() => address.set_ip(Ipv6Addr::LOCALHOST.into()),
+                _ => {}
+            }
+            address
+        });
+        self
+    }
+
+    // Request the server to shut down.
+    pub fn shutdown(&self) {
+        if self.requested.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        // The connection is only used to return from accept, so it is closed right away.
+        if let Some(address) = &self.wake_address {
+            let _ = TcpStream::connect_timeout(address, Duration::from_secs(1));
+        }
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Server;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn shutdown_wakes_a_running_server() {
+        let mut server = Server::builder("127.0.0.1:0").no_log().build().unwrap();
+        let handle = server.shutdown_handle();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || sender.send(server.run().is_ok()));
+        thread::sleep(Duration::from_millis(100));
+        handle.shutdown();
+        let returned = receiver.recv_timeout(Duration::from_secs(2));
+        assert_eq!(returned, Ok(true));
+    }
+
+    #[test]
+    fn wakes_listeners_on_every_interface_through_loopback() {
+        let handle = ShutdownHandle::new().waking("0.0.0.0:80".parse().ok());
+        assert_eq!(handle.wake_address, "127.0.0.1:80".parse().ok());
+        let handle = ShutdownHandle::new().waking("[::]:80".parse().ok());
+        assert_eq!(handle.wake_address, "[::1]:80".parse().ok());
+    }
+}
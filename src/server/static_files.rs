@@ -0,0 +1,426 @@
+use std::fs::{File, Metadata};
+use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::communication::error::HttpError;
+use crate::communication::method::Method;
+use crate::communication::request::Request;
+use crate::communication::response::Response;
+use crate::utils::date::{format_http_date, parse_http_date};
+use crate::utils::file::resolve_file_path;
+use crate::utils::guess::guess_mime_type;
+use crate::utils::range::parse_byte_ranges;
+
+// Settings for serving the files of a directory, registered with Server::serve_static_files.
+// GET requests for a path that exists in the directory are served before any router is matched, other requests are passed on to the routers.
+// Files therefore take precedence over routes with the same path, any file in the directory can be requested (apart from hidden files).
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    dir: PathBuf,
+    allow_hidden: bool,
+    index_files: Vec<String>,
+    stream_threshold: u64,
+}
+
+// What a request path resolves to in the served directory.
+enum StaticTarget {
+    File(PathBuf),
+    // A directory requested without a trailing slash, redirected to the path with one (without the query string).
+    Redirect(String),
+}
+
+impl StaticFiles {
+    // Serve the files of the directory, an absolute path or relative to the root path of the server.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            allow_hidden: false,
+            index_files: vec![String::from("index.html")],
+            stream_threshold: 1024 * 1024,
+        }
+    }
+
+    // Allow serving hidden files and files in hidden directories (names starting with "."), refused by default.
+    pub fn allow_hidden(mut self, allow: bool) -> Self {
+        self.allow_hidden = allow;
+        self
+    }
+
+    // Set the file names served for a directory path ("/docs/"), tried in order. Defaults to "index.html".
+    pub fn index_files(mut self, names: &[&str]) -> Self {
+        self.index_files = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    // Set the size in bytes above which files are streamed to the client while sending, instead of being read into memory first. Defaults to 1 MiB.
+    pub fn stream_threshold(mut self, size: u64) -> Self {
+        self.stream_threshold = size;
+        self
+    }
+
+    // Resolve the served directory against the root path of the server.
+    pub(crate) fn with_root(mut self, root_path: &Path) -> Self {
+        self.dir = root_path.join(&self.dir);
+        self
+    }
+
+    // Respond to the request if it is for a file or directory in the served directory. Returns false if the request should be passed on to the routers.
+    // Paths leaving the served directory are refused with 403 Forbidden. Files are sent with ETag and Last-Modified headers, and 304 Not Modified is sent if the client already has the current version.
+    // Parts of a file can be requested with the Range header, sent as 206 Partial Content (multipart/byteranges for multiple ranges).
+    pub(crate) fn serve(
+        &self,
+        request: &Request,
+        response: &mut Response,
+    ) -> Result<bool, HttpError> {
+        if !matches!(request.method, Method::GET(_)) {
+            return Ok(false);
+        }
+        match self.find(&request.path)? {
+            Some(StaticTarget::File(path)) => {
+                let file = File::open(&path)?;
+                let metadata = file.metadata()?;
+                let (etag, modified) = Self::file_version(&metadata);
+                response.insert_header("ETag", &etag);
+                if let Some(modified) = modified {
+                    response.insert_header("Last-Modified", &format_http_date(modified));
+                }
+                if Self::is_not_modified(request, &etag, modified) {
+                    response.set_status(304, "Not Modified");
+                    return Ok(true);
+                }
+                let extension = path
+                    .extension()
+                    .map(|extension| extension.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let content_type = guess_mime_type(&extension);
+                response.insert_header("Accept-Ranges", "bytes");
+                let length = metadata.len();
+                match Self::requested_ranges(request, &etag, modified, length) {
+                    Some(ranges) => {
+                        self.set_ranges(response, &content_type, &path, length, &ranges)?
+                    }
+                    None => {
+                        self.set_file_content(response, &content_type, Box::new(file), length)?
+                    }
+                }
+                Ok(true)
+            }
+            Some(StaticTarget::Redirect(mut location)) => {
+                if !request.query_string.is_empty() {
+                    location = format!("{}?{}", location, request.query_string);
+                }
+                response.set_status(301, "Moved Permanently");
+                response.insert_header("Location", &location);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // Get the entity tag and the last modification time (in whole seconds, as sent in Last-Modified) of a file.
+    // The entity tag is based on the size and the modification time, so it changes whenever the file is replaced or written to.
+    fn file_version(metadata: &Metadata) -> (String, Option<SystemTime>) {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_secs());
+        let etag = format!(
+            "\"{:x}-{:x}\"",
+            metadata.len(),
+            modified.unwrap_or_default()
+        );
+        let modified = modified.map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds));
+        (etag, modified)
+    }
+
+    // Check if the version of the file the client has (If-None-Match or If-Modified-Since) is still current. If-None-Match takes precedence.
+    fn is_not_modified(request: &Request, etag: &str, modified: Option<SystemTime>) -> bool {
+        let if_none_match = request.headers.get_all("If-None-Match");
+        if !if_none_match.is_empty() {
+            // Entity tags are compared weakly, so "W/" prefixed tags also match.
+            return if_none_match
+                .iter()
+                .flat_map(|value| value.split(','))
+                .map(|tag| tag.trim())
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+        }
+        let since = request
+            .headers
+            .get("If-Modified-Since")
+            .and_then(parse_http_date);
+        match (since, modified) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => false,
+        }
+    }
+
+    // Get the byte ranges requested with the Range header, or None if the whole file should be sent.
+    // With If-Range, the ranges are only sent if the file still has the given entity tag or modification time.
+    fn requested_ranges(
+        request: &Request,
+        etag: &str,
+        modified: Option<SystemTime>,
+        length: u64,
+    ) -> Option<Vec<(u64, u64)>> {
+        let range = request.headers.get("Range")?;
+        if let Some(if_range) = request.headers.get("If-Range") {
+            let if_range = if_range.trim();
+            let is_current = match parse_http_date(if_range) {
+                Some(date) => modified == Some(date),
+                // Entity tags are compared strongly, so weak ("W/") tags never match.
+                None => if_range == etag,
+            };
+            if !is_current {
+                return None;
+            }
+        }
+        parse_byte_ranges(range, length)
+    }
+
+    // Respond with the requested ranges of the file, or 416 Range Not Satisfiable if none of them is in the file.
+    fn set_ranges(
+        &self,
+        response: &mut Response,
+        content_type: &str,
+        path: &Path,
+        length: u64,
+        ranges: &[(u64, u64)],
+    ) -> io::Result<()> {
+        match ranges {
+            [] => {
+                response.set_status(416, "Range Not Satisfiable");
+                response.insert_header("Content-Range", &format!("bytes */{}", length));
+                response.set_text("Range Not Satisfiable");
+                Ok(())
+            }
+            [(start, end)] => {
+                response.set_status(206, "Partial Content");
+                let content_range = format!("bytes {}-{}/{}", start, end, length);
+                response.insert_header("Content-Range", &content_range);
+                let reader = Self::open_range(path, *start, *end)?;
+                self.set_file_content(response, content_type, reader, end - start + 1)
+            }
+            _ => {
+                let boundary = format!(
+                    "{:x}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_nanos()
+                );
+                // The parts are chained into one reader, so the ranges are not read into memory if the body is streamed.
+                let mut body: Box<dyn Read + Send> = Box::new(io::empty());
+                let mut body_length = 0;
+                for (start, end) in ranges {
+                    let part_head = format!(
+                        "--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                        boundary, content_type, start, end, length
+                    );
+                    body_length += part_head.len() as u64 + (end - start + 1) + 2;
+                    body = Box::new(
+                        body.chain(Cursor::new(part_head))
+                            .chain(Self::open_range(path, *start, *end)?)
+                            .chain(Cursor::new("\r\n")),
+                    );
+                }
+                let closing = format!("--{}--\r\n", boundary);
+                body_length += closing.len() as u64;
+                body = Box::new(body.chain(Cursor::new(closing)));
+                response.set_status(206, "Partial Content");
+                let content_type = format!("multipart/byteranges; boundary={}", boundary);
+                self.set_file_content(response, &content_type, body, body_length)
+            }
+        }
+    }
+
+    // Open the file for reading the bytes from start to end (inclusive).
+    fn open_range(path: &Path, start: u64, end: u64) -> io::Result<Box<dyn Read + Send>> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(start))?;
+        Ok(Box::new(file.take(end - start + 1)))
+    }
+
+    // Set content read from a file as the content of the response. Content larger than the stream threshold is streamed while sending.
+    fn set_file_content(
+        &self,
+        response: &mut Response,
+        content_type: &str,
+        reader: Box<dyn Read + Send>,
+        length: u64,
+    ) -> io::Result<()> {
+        if length > self.stream_threshold {
+            response.set_stream(content_type, reader, length);
+            return Ok(());
+        }
+        let mut content = Vec::with_capacity(length as usize);
+        reader.take(length).read_to_end(&mut content)?;
+        response.set_bytes(content_type, content);
+        Ok(())
+    }
+
+    // Find the file of a request path. Directories resolve to their first existing index file, or to a redirect if the path has no trailing slash.
+    fn find(&self, url_path: &str) -> Result<Option<StaticTarget>, HttpError> {
+        let Some(path) = self.resolve(url_path)? else {
+            return Ok(None);
+        };
+        if path.is_file() {
+            return Ok(Some(StaticTarget::File(path)));
+        }
+        if !url_path.ends_with('/') {
+            // The location is built from the segments the path was resolved from, as a raw path starting with "//" or "/\\" would redirect to another host.
+            let mut location = String::from("/");
+            for segment in url_path
+                .split(['/', '\\'])
+                .filter(|segment| !segment.is_empty() && *segment != ".")
+            {
+                location.push_str(segment);
+                location.push('/');
+            }
+            return Ok(Some(StaticTarget::Redirect(location)));
+        }
+        for index_file in &self.index_files {
+            if let Some(path) = self.resolve(&format!("{}{}", url_path, index_file))? {
+                if path.is_file() {
+                    return Ok(Some(StaticTarget::File(path)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // Resolve a request path to an existing path in the served directory. Missing and hidden files resolve to None.
+    fn resolve(&self, url_path: &str) -> Result<Option<PathBuf>, HttpError> {
+        match resolve_file_path(&self.dir, url_path, self.allow_hidden) {
+            Ok(path) => Ok(Some(path)),
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                log!("Static File Retrieval Error: {:#?}", e);
+                Err(HttpError::forbidden("Forbidden"))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::communication::headers::Headers;
+    use std::collections::HashMap;
+    use std::fs;
+
+    // Create an empty directory for a test, removed again by the test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "tiny-rust-server-static-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn request(path: &str, query_string: &str) -> Request {
+        Request {
+            method: Method::default(),
+            path: path.to_string(),
+            version: String::from("HTTP/1.1"),
+            query_string: query_string.to_string(),
+            query: HashMap::new(),
+            params: HashMap::new(),
+            headers: Headers::new(),
+            trailers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn redirect_location(files: &StaticFiles, path: &str, query_string: &str) -> Option<String> {
+        let mut response = Response::new();
+        assert!(files
+            .serve(&request(path, query_string), &mut response)
+            .unwrap());
+        assert_eq!(response.status_code, 301);
+        response.headers.get("Location").map(str::to_string)
+    }
+
+    #[test]
+    fn redirects_directories_to_a_trailing_slash() {
+        let dir = temp_dir("redirect");
+        fs::create_dir_all(dir.join("docs/sub")).unwrap();
+        let files = StaticFiles::new(&dir);
+        let location = |path: &str| redirect_location(&files, path, "");
+        assert_eq!(location("/docs").as_deref(), Some("/docs/"));
+        assert_eq!(location("/docs/sub").as_deref(), Some("/docs/sub/"));
+        assert_eq!(location("/docs/./sub").as_deref(), Some("/docs/sub/"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn redirects_only_to_a_local_path() {
+        let dir = temp_dir("redirect-local");
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        let files = StaticFiles::new(&dir);
+        for path in ["//docs", "///docs", "/\\docs", "\\\\docs", "//./docs"] {
+            let location = redirect_location(&files, path, "");
+            assert_eq!(location.as_deref(), Some("/docs/"), "{path:?}");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn redirects_with_the_query_string() {
+        let dir = temp_dir("redirect-query");
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        let files = StaticFiles::new(&dir);
+        let location = redirect_location(&files, "/docs", "x=1&y=%20");
+        assert_eq!(location.as_deref(), Some("/docs/?x=1&y=%20"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn serves_index_files_of_directories() {
+        let dir = temp_dir("index");
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("docs/index.html"), "<h1>Docs</h1>").unwrap();
+        let files = StaticFiles::new(&dir);
+        let mut response = Response::new();
+        assert!(files.serve(&request("/docs/", ""), &mut response).unwrap());
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.content_type.as_deref(), Some("text/html"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_paths_leaving_the_directory() {
+        let dir = temp_dir("traversal");
+        fs::create_dir_all(dir.join("public")).unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+        let files = StaticFiles::new(dir.join("public"));
+        for path in ["/../secret.txt", "/%2e%2e/secret.txt", "/..\\secret.txt"] {
+            let mut response = Response::new();
+            let error = files.serve(&request(path, ""), &mut response).unwrap_err();
+            assert_eq!(error.status_code, 403, "{path:?}");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn passes_hidden_and_missing_files_on_to_the_routers() {
+        let dir = temp_dir("hidden");
+        fs::write(dir.join(".env"), "hidden").unwrap();
+        let files = StaticFiles::new(&dir);
+        for path in ["/.env", "/missing.txt", "/a%2Fb"] {
+            let mut response = Response::new();
+            assert!(
+                !files.serve(&request(path, ""), &mut response).unwrap(),
+                "{path:?}"
+            );
+        }
+        let files = StaticFiles::new(&dir).allow_hidden(true);
+        let mut response = Response::new();
+        assert!(files.serve(&request("/.env", ""), &mut response).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,9 @@
+pub mod date;
 pub mod file;
 pub mod general;
 pub mod guess;
+pub mod range;
+pub mod signal;
 pub mod stream;
 pub mod thread_pool;
+pub mod url;
@@ -0,0 +1,193 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Format a time as a HTTP date ("Sun, 06 Nov 1994 08:49:37 GMT"), as used by Last-Modified. Times before 1970 are formatted as the epoch.
+pub fn format_http_date(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days = seconds / 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAY_NAMES[(days % 7) as usize],
+        day,
+        MONTH_NAMES[(month - 1) as usize],
+        year,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+// Parse a HTTP date in the preferred format ("Sun, 06 Nov 1994 08:49:37 GMT"). Returns None for other (obsolete) formats and invalid dates.
+pub fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let (_, date) = date.trim().split_once(", ")?;
+    let parts = date.split(' ').collect::<Vec<&str>>();
+    let [day, month, year, time, "GMT"] = parts[..] else {
+        return None;
+    };
+    let day = day.parse::<u64>().ok()?;
+    let month = MONTH_NAMES.iter().position(|name| *name == month)? as u64 + 1;
+    let year = year.parse::<u64>().ok()?;
+    let time = time
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    let [hours, minutes, seconds] = time[..] else {
+        return None;
+    };
+    if year < 1970 || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    // Days past the end of the month (e.g. 30 Feb) would roll over into the next month.
+    if civil_from_days(days) != (year, month, day) {
+        return None;
+    }
+    let seconds = days * 86400 + hours * 3600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+// Convert days since 1970-01-01 to a (year, month, day) date of the proleptic Gregorian calendar.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Shift the epoch to 0000-03-01, so leap days are at the end of a year.
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+// Convert a (year, month, day) date of the proleptic Gregorian calendar (from 1970) to days since 1970-01-01.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(
+            format_http_date(UNIX_EPOCH),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+        assert_eq!(
+            format_http_date(time(784111777)),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        // Fractions of a second are dropped.
+        let fraction = time(784111777) + Duration::from_millis(999);
+        assert_eq!(format_http_date(fraction), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn formats_times_before_1970_as_the_epoch() {
+        let before = UNIX_EPOCH - Duration::from_secs(1);
+        assert_eq!(format_http_date(before), "Thu, 01 Jan 1970 00:00:00 GMT");
+    }
+
+    #[test]
+    fn formats_leap_days() {
+        assert_eq!(
+            format_http_date(time(951782400)),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+        assert_eq!(
+            format_http_date(time(1709164800)),
+            "Thu, 29 Feb 2024 00:00:00 GMT"
+        );
+        assert_eq!(
+            format_http_date(time(1709251199)),
+            "Thu, 29 Feb 2024 23:59:59 GMT"
+        );
+        assert_eq!(
+            format_http_date(time(1709251200)),
+            "Fri, 01 Mar 2024 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn parses_dates() {
+        let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(date, Some(time(784111777)));
+        let date = parse_http_date(" Thu, 29 Feb 2024 00:00:00 GMT ");
+        assert_eq!(date, Some(time(1709164800)));
+    }
+
+    #[test]
+    fn round_trips() {
+        // Roughly every 1.2 days from 1970 to 2106, so every day of the month and every leap day is passed over time.
+        for seconds in (0..u32::MAX as u64).step_by(104_729) {
+            let formatted = format_http_date(time(seconds));
+            assert_eq!(
+                parse_http_date(&formatted),
+                Some(time(seconds)),
+                "{formatted}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_leap_days_only_in_leap_years() {
+        assert!(parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT").is_some());
+        assert!(parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT").is_some());
+        // Years divisible by 100 but not by 400 are not leap years.
+        assert!(parse_http_date("Mon, 29 Feb 2100 00:00:00 GMT").is_none());
+        assert!(parse_http_date("Wed, 29 Feb 2023 00:00:00 GMT").is_none());
+    }
+
+    #[test]
+    fn rejects_days_past_the_end_of_the_month() {
+        assert!(parse_http_date("Sat, 30 Apr 2024 00:00:00 GMT").is_some());
+        assert!(parse_http_date("Sun, 31 Apr 2024 00:00:00 GMT").is_none());
+        assert!(parse_http_date("Fri, 30 Feb 2024 00:00:00 GMT").is_none());
+        assert!(parse_http_date("Thu, 00 Jan 2024 00:00:00 GMT").is_none());
+        assert!(parse_http_date("Thu, 32 Jan 2024 00:00:00 GMT").is_none());
+    }
+
+    #[test]
+    fn rejects_other_formats_and_invalid_dates() {
+        for date in [
+            "",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 06 Nov 1994 08:49:37",
+            "Sun, 06 nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:60:00 GMT",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Wed, 31 Dec 1969 23:59:59 GMT",
+        ] {
+            assert_eq!(parse_http_date(date), None, "{date:?}");
+        }
+    }
+}
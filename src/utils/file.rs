@@ -1,26 +1,236 @@
-use std::fs;
-use std::io::Error;
-use std::path::Path;
-
-// Gets the first HTML file name in a directory.
-pub fn get_first_html_file_name(path: &Path) -> Result<(String, String), Error> {
-    let paths = fs::read_dir(path)?;
-    for path in paths {
-        let path = path?.path();
-        match (path.is_file(), path.extension()) {
-            (true, Some(extension)) => {
-                if extension == "html" {
-                    return Ok((
-                        path.file_name().unwrap().to_string_lossy().to_string(),
-                        path.extension().unwrap().to_string_lossy().to_string(),
-                    ));
-                }
-            }
-            _ => continue,
-        }
-    }
-    Err(Error::new(
-        std::io::ErrorKind::NotFound,
-        "No HTML files found.",
-    ))
+use std::io::{Error, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+
+use crate::utils::url::percent_decode;
+
+// Resolve a percent-encoded URL path (e.g. "/css/style.css") to a file inside the root directory. Every segment is decoded separately.
+// Fails with PermissionDenied if the path would leave the root (".." segments, drive prefixes or symbolic links pointing outside), and with NotFound if the file does not exist, is hidden (a segment starting with ".") while hidden files are not allowed or a segment contains an encoded separator.
+pub fn resolve_file_path(
+    root: &Path,
+    url_path: &str,
+    allow_hidden: bool,
+) -> Result<PathBuf, Error> {
+    let mut path = root.to_path_buf();
+    for segment in url_path
+        .split(['/', '\\'])
+        .map(|segment| percent_decode(segment, false))
+        .filter(|segment| !segment.is_empty() && segment != ".")
+    {
+        // A file name can not contain a separator, so a segment with an encoded one ("%2F") names no file.
+        if segment.contains(['/', '\\']) {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Encoded separator in path: {}", url_path),
+            ));
+        }
+        // Anything other than a plain name (e.g. ".." or a Windows drive prefix) could leave the root.
+        let is_name = Path::new(&segment)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if !is_name || segment.contains('\0') {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Path escapes the served directory: {}", url_path),
+            ));
+        }
+        if segment.starts_with('.') && !allow_hidden {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Hidden file refused: {}", url_path),
+            ));
+        }
+        path.push(&segment);
+    }
+    let root = root.canonicalize()?;
+    let path = path.canonicalize()?;
+    if !path.starts_with(&root) {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!("Path escapes the served directory: {}", url_path),
+        ));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Create a served "root" directory with a few files, next to a "secret.txt" outside of it. Removed again by the test.
+    fn temp_root(name: &str) -> (PathBuf, PathBuf) {
+        let base = std::env::temp_dir().join(format!(
+            "tiny-rust-server-file-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&base);
+        let root = base.join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("sub/b.txt"), "b").unwrap();
+        fs::write(root.join(".env"), "hidden").unwrap();
+        fs::write(root.join(".git/config"), "hidden").unwrap();
+        fs::write(base.join("secret.txt"), "secret").unwrap();
+        (base, root)
+    }
+
+    fn error_kind(root: &Path, url_path: &str, allow_hidden: bool) -> ErrorKind {
+        match resolve_file_path(root, url_path, allow_hidden) {
+            Ok(path) => panic!("{url_path:?} resolved to {path:?}"),
+            Err(e) => e.kind(),
+        }
+    }
+
+    #[test]
+    fn resolves_files_in_the_root() {
+        let (base, root) = temp_root("resolve");
+        let canonical = root.canonicalize().unwrap();
+        let resolve = |url_path: &str| resolve_file_path(&root, url_path, false).unwrap();
+        assert_eq!(resolve("/a.txt"), canonical.join("a.txt"));
+        assert_eq!(resolve("/sub/b.txt"), canonical.join("sub/b.txt"));
+        assert_eq!(resolve("//sub/./b.txt"), canonical.join("sub/b.txt"));
+        assert_eq!(resolve("/%73ub/b%2Etxt"), canonical.join("sub/b.txt"));
+        assert_eq!(resolve("/"), canonical);
+        assert_eq!(
+            error_kind(&root, "/missing.txt", false),
+            ErrorKind::NotFound
+        );
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn refuses_parent_segments() {
+        let (base, root) = temp_root("parent");
+        for url_path in [
+            "/../secret.txt",
+            "/sub/../../secret.txt",
+            "/sub/../a.txt",
+            "/..",
+        ] {
+            assert_eq!(
+                error_kind(&root, url_path, false),
+                ErrorKind::PermissionDenied,
+                "{url_path:?}"
+            );
+            assert_eq!(
+                error_kind(&root, url_path, true),
+                ErrorKind::PermissionDenied,
+                "{url_path:?}"
+            );
+        }
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn refuses_encoded_parent_segments() {
+        let (base, root) = temp_root("encoded-parent");
+        for url_path in [
+            "/%2e%2e/secret.txt",
+            "/%2E%2E/secret.txt",
+            "/.%2e/secret.txt",
+            "/sub/%2e./%2e%2e/secret.txt",
+        ] {
+            assert_eq!(
+                error_kind(&root, url_path, false),
+                ErrorKind::PermissionDenied,
+                "{url_path:?}"
+            );
+        }
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn refuses_encoded_separators() {
+        let (base, root) = temp_root("encoded-separator");
+        for url_path in [
+            "/sub%2Fb.txt",
+            "/sub%2fb.txt",
+            "/sub%5Cb.txt",
+            "/..%2Fsecret.txt",
+            "/..%5csecret.txt",
+        ] {
+            assert_eq!(
+                error_kind(&root, url_path, false),
+                ErrorKind::NotFound,
+                "{url_path:?}"
+            );
+        }
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn treats_backslashes_as_separators() {
+        let (base, root) = temp_root("backslash");
+        let path = resolve_file_path(&root, "/sub\\b.txt", false).unwrap();
+        assert_eq!(path, root.canonicalize().unwrap().join("sub/b.txt"));
+        for url_path in [
+            "/..\\secret.txt",
+            "\\..\\secret.txt",
+            "/sub\\..\\..\\secret.txt",
+        ] {
+            assert_eq!(
+                error_kind(&root, url_path, false),
+                ErrorKind::PermissionDenied,
+                "{url_path:?}"
+            );
+        }
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn refuses_nul_bytes() {
+        let (base, root) = temp_root("nul");
+        for url_path in ["/a.txt%00.html", "/a.txt\0", "/%00"] {
+            assert_eq!(
+                error_kind(&root, url_path, false),
+                ErrorKind::PermissionDenied,
+                "{url_path:?}"
+            );
+        }
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn refuses_hidden_files_unless_allowed() {
+        let (base, root) = temp_root("hidden");
+        for url_path in ["/.env", "/.git/config", "/%2Eenv", "/.git/"] {
+            assert_eq!(
+                error_kind(&root, url_path, false),
+                ErrorKind::NotFound,
+                "{url_path:?}"
+            );
+        }
+        let canonical = root.canonicalize().unwrap();
+        assert_eq!(
+            resolve_file_path(&root, "/.env", true).unwrap(),
+            canonical.join(".env")
+        );
+        let path = resolve_file_path(&root, "/.git/config", true).unwrap();
+        assert_eq!(path, canonical.join(".git/config"));
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_symbolic_links_leaving_the_root() {
+        use std::os::unix::fs::symlink;
+
+        let (base, root) = temp_root("symlink");
+        symlink(base.join("secret.txt"), root.join("secret-link.txt")).unwrap();
+        symlink(&base, root.join("base-link")).unwrap();
+        symlink(root.join("sub/b.txt"), root.join("inside-link.txt")).unwrap();
+        for url_path in ["/secret-link.txt", "/base-link/secret.txt", "/base-link/"] {
+            assert_eq!(
+                error_kind(&root, url_path, false),
+                ErrorKind::PermissionDenied,
+                "{url_path:?}"
+            );
+        }
+        // Links staying inside the root are followed.
+        let path = resolve_file_path(&root, "/inside-link.txt", false).unwrap();
+        assert_eq!(path, root.canonicalize().unwrap().join("sub/b.txt"));
+        fs::remove_dir_all(&base).unwrap();
+    }
 }
@@ -1,6 +1,40 @@
-pub fn is_static_file(file_extension: &str) -> bool {
-    match file_extension.to_lowercase().as_str() {
-        "html" | "css" | "js" | "png" | "jpg" | "jpeg" | "gif" | "ico" => true,
-        _ => false,
+use std::any::Any;
+
+// Get the standard status message (reason phrase) of a HTTP status code.
+pub fn get_status_message(status_code: usize) -> &'static str {
+    match status_code {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+// Get the message of a caught panic, panics raised with panic!("...") have a string payload.
+pub fn get_panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("Unknown panic")
     }
 }
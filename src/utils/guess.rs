@@ -1,13 +1,58 @@
-// Guess the mime type of a file based on its extension
+// Guess the mime type of a file based on its extension. Unknown extensions are sent as binary data, so the client does not try to display them.
 pub fn guess_mime_type(extension: &str) -> String {
     String::from(match extension.to_lowercase().as_str() {
-        "html" => "text/html",
+        "html" | "htm" => "text/html",
         "css" => "text/css",
-        "js" => "text/javascript",
+        "js" | "mjs" => "text/javascript",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "md" => "text/markdown",
+        "xml" => "application/xml",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
         "png" => "image/png",
         "jpg" | "jpeg" => "image/jpeg",
         "gif" => "image/gif",
         "ico" => "image/x-icon",
-        _ => "text/plain",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "bmp" => "image/bmp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" | "oga" => "audio/ogg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        _ => "application/octet-stream",
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guesses_common_types() {
+        assert_eq!(guess_mime_type("html"), "text/html");
+        assert_eq!(guess_mime_type("SVG"), "image/svg+xml");
+        assert_eq!(guess_mime_type("woff2"), "font/woff2");
+        assert_eq!(guess_mime_type("mp4"), "video/mp4");
+        assert_eq!(guess_mime_type("pdf"), "application/pdf");
+    }
+
+    #[test]
+    fn sends_unknown_types_as_binary_data() {
+        assert_eq!(guess_mime_type("exe"), "application/octet-stream");
+        assert_eq!(guess_mime_type(""), "application/octet-stream");
+    }
+}
@@ -0,0 +1,147 @@
+// Maximum number of ranges accepted in one Range header, requests with more ranges get the whole content.
+const MAX_RANGES: usize = 32;
+
+// Parse a Range header ("bytes=0-99,200-,-500") against the length of the content, into inclusive (start, end) byte offsets of the satisfiable ranges.
+// Returns None if the header is invalid or not in bytes, in which case it should be ignored. An empty list means none of the ranges can be satisfied.
+pub fn parse_byte_ranges(header: &str, length: u64) -> Option<Vec<(u64, u64)>> {
+    let (unit, specs) = header.trim().split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+    let specs = specs
+        .split(',')
+        .map(|spec| spec.trim())
+        .collect::<Vec<&str>>();
+    if specs.len() > MAX_RANGES {
+        return None;
+    }
+    let mut ranges = Vec::new();
+    for spec in specs {
+        let (start, end) = spec.split_once('-')?;
+        let range = match (start, end) {
+            // A suffix range ("-500") is the last bytes of the content.
+            ("", suffix) => {
+                let suffix = parse_position(suffix)?;
+                (suffix > 0 && length > 0).then(|| (length.saturating_sub(suffix), length - 1))
+            }
+            (start, end) => {
+                let start = parse_position(start)?;
+                let end = match end {
+                    "" => u64::MAX,
+                    end => parse_position(end)?,
+                };
+                if end < start {
+                    return None;
+                }
+                (start < length).then(|| (start, end.min(length - 1)))
+            }
+        };
+        ranges.extend(range);
+    }
+    Some(ranges)
+}
+
+// Parse a byte position, which only consists of digits (a sign, allowed by u64::from_str, is not).
+fn parse_position(position: &str) -> Option<u64> {
+    if position.is_empty() || !position.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    position.parse::<u64>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_closed_ranges() {
+        assert_eq!(parse_byte_ranges("bytes=0-99", 1000), Some(vec![(0, 99)]));
+        assert_eq!(parse_byte_ranges("bytes=5-5", 1000), Some(vec![(5, 5)]));
+        // The end is limited to the last byte.
+        assert_eq!(
+            parse_byte_ranges("bytes=900-2000", 1000),
+            Some(vec![(900, 999)])
+        );
+    }
+
+    #[test]
+    fn parses_open_ended_ranges() {
+        assert_eq!(
+            parse_byte_ranges("bytes=200-", 1000),
+            Some(vec![(200, 999)])
+        );
+        assert_eq!(
+            parse_byte_ranges("bytes=999-", 1000),
+            Some(vec![(999, 999)])
+        );
+        assert_eq!(parse_byte_ranges("bytes=1000-", 1000), Some(vec![]));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(
+            parse_byte_ranges("bytes=-500", 1000),
+            Some(vec![(500, 999)])
+        );
+        // A suffix longer than the content is the whole content.
+        assert_eq!(parse_byte_ranges("bytes=-5000", 1000), Some(vec![(0, 999)]));
+        assert_eq!(parse_byte_ranges("bytes=-0", 1000), Some(vec![]));
+    }
+
+    #[test]
+    fn parses_multiple_ranges_and_skips_unsatisfiable_ones() {
+        let ranges = parse_byte_ranges("bytes=0-9, 2000-3000 ,-10", 1000);
+        assert_eq!(ranges, Some(vec![(0, 9), (990, 999)]));
+        let ranges = parse_byte_ranges("bytes=2000-3000,5000-", 1000);
+        assert_eq!(ranges, Some(vec![]));
+    }
+
+    #[test]
+    fn nothing_is_satisfiable_for_empty_content() {
+        assert_eq!(parse_byte_ranges("bytes=0-99", 0), Some(vec![]));
+        assert_eq!(parse_byte_ranges("bytes=0-", 0), Some(vec![]));
+        assert_eq!(parse_byte_ranges("bytes=-500", 0), Some(vec![]));
+    }
+
+    #[test]
+    fn ignores_ranges_ending_before_they_start() {
+        assert_eq!(parse_byte_ranges("bytes=10-9", 1000), None);
+        assert_eq!(parse_byte_ranges("bytes=0-9,10-9", 1000), None);
+    }
+
+    #[test]
+    fn ignores_invalid_headers() {
+        for header in [
+            "",
+            "bytes",
+            "bytes=",
+            "bytes=-",
+            "bytes=abc-",
+            "bytes=0-9-",
+            "bytes=+0-9",
+            "bytes=0-+9",
+            "bytes= 0 - 9",
+            "bytes=0x0-9",
+            "items=0-9",
+            "bytes=99999999999999999999-",
+        ] {
+            assert_eq!(parse_byte_ranges(header, 1000), None, "{header:?}");
+        }
+    }
+
+    #[test]
+    fn accepts_the_unit_case_insensitively() {
+        assert_eq!(parse_byte_ranges(" Bytes=0-0", 1000), Some(vec![(0, 0)]));
+    }
+
+    #[test]
+    fn ignores_headers_with_too_many_ranges() {
+        let header = format!("bytes={}", vec!["0-0"; MAX_RANGES].join(","));
+        assert_eq!(
+            parse_byte_ranges(&header, 1000).map(|ranges| ranges.len()),
+            Some(MAX_RANGES)
+        );
+        let header = format!("bytes={}", vec!["0-0"; MAX_RANGES + 1].join(","));
+        assert_eq!(parse_byte_ranges(&header, 1000), None);
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static RECEIVED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+mod unix {
+    use super::RECEIVED;
+    use std::sync::atomic::Ordering;
+
+    const SIGINT: i32 = 2;
+    const SIGTERM: i32 = 15;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+
+    // Only async-signal-safe operations are allowed here, so the handler just sets a flag.
+    extern "C" fn handle(_signum: i32) {
+        RECEIVED.store(true, Ordering::SeqCst);
+    }
+
+    pub fn listen() -> bool {
+        unsafe { signal(SIGINT, handle) != usize::MAX && signal(SIGTERM, handle) != usize::MAX }
+    }
+}
+
+// Start listening for SIGINT (Ctrl+C) and SIGTERM. Returns false if the signals are not supported on this platform.
+pub fn listen_for_termination() -> bool {
+    #[cfg(unix)]
+    return unix::listen();
+    #[cfg(not(unix))]
+    return false;
+}
+
+// Check if SIGINT or SIGTERM has been received since listening started.
+pub fn is_termination_received() -> bool {
+    RECEIVED.load(Ordering::SeqCst)
+}
@@ -1,20 +1,220 @@
 use std::error::Error;
-use std::io::{BufRead, BufReader};
-use std::net::TcpStream;
+use std::io::{self, BufRead, ErrorKind, Read};
 
-pub fn read_stream_lines(stream: &TcpStream) -> Result<Vec<String>, Box<dyn Error>> {
-    let buf_reader = BufReader::new(stream);
+// Read lines from the stream until the first empty line (end of the HTTP head). Fails if the lines are longer than `max_size` bytes in total.
+pub fn read_stream_lines(
+    reader: &mut impl BufRead,
+    max_size: usize,
+) -> Result<Vec<String>, Box<dyn Error>> {
     let mut lines: Vec<String> = Vec::new();
-    for line in buf_reader.lines() {
-        match line {
-            Ok(line) => {
-                if line.is_empty() {
-                    break;
-                };
-                lines.push(line);
+    let mut size = 0;
+    loop {
+        let mut line: Vec<u8> = Vec::new();
+        let read = reader
+            .by_ref()
+            .take(max_size.saturating_sub(size) as u64)
+            .read_until(b'\n', &mut line)?;
+        size += read;
+        if !line.ends_with(b"\n") {
+            if size >= max_size {
+                return Err(Box::new(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Request head exceeds the maximum allowed size",
+                )));
             }
-            Err(e) => return Err(Box::new(e)),
+            // The stream ended, a partial line is kept as is.
+            if !line.is_empty() {
+                lines.push(String::from_utf8_lossy(&line).to_string());
+            }
+            break;
+        }
+        line.pop();
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+        if line.is_empty() {
+            break;
+        }
+        match String::from_utf8(line) {
+            Ok(line) => lines.push(line),
+            Err(e) => return Err(Box::new(io::Error::new(ErrorKind::InvalidData, e))),
         }
     }
     Ok(lines)
 }
+
+// Read exactly `length` bytes from the stream.
+pub fn read_stream_bytes(
+    reader: &mut impl BufRead,
+    length: usize,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < length {
+        return Err(Box::new(io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "Stream ended before the expected amount of bytes was read",
+        )));
+    }
+    Ok(bytes)
+}
+
+// Maximum length of a single chunk size line in a chunked body.
+const MAX_CHUNK_LINE_LENGTH: u64 = 4096;
+// Maximum size of the trailer lines following a chunked body.
+const MAX_TRAILERS_SIZE: usize = 8192;
+
+// Read a chunked transfer-encoded body from the stream. Returns the decoded bytes and the trailer lines.
+pub fn read_chunked_bytes(
+    reader: &mut impl BufRead,
+    max_size: usize,
+) -> Result<(Vec<u8>, Vec<String>), Box<dyn Error>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    loop {
+        let line = read_chunk_line(reader)?;
+        // Chunk extensions (";name=value") are allowed after the size, but are ignored.
+        let size = line.split(';').next().unwrap_or_default().trim();
+        if size.is_empty() || !size.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::InvalidData,
+                "Malformed chunk size",
+            )));
+        }
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Malformed chunk size"))?;
+        if size == 0 {
+            break;
+        }
+        if bytes.len().saturating_add(size) > max_size {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::FileTooLarge,
+                "Chunked body exceeds the maximum allowed size",
+            )));
+        }
+        bytes.extend(read_stream_bytes(reader, size)?);
+        if !read_chunk_line(reader)?.is_empty() {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::InvalidData,
+                "Missing line break after chunk data",
+            )));
+        }
+    }
+    let trailers = read_stream_lines(reader, MAX_TRAILERS_SIZE)?;
+    Ok((bytes, trailers))
+}
+
+// Read a single CRLF terminated line of a chunked body, without the line break.
+fn read_chunk_line(reader: &mut impl BufRead) -> Result<String, Box<dyn Error>> {
+    let mut line: Vec<u8> = Vec::new();
+    reader
+        .take(MAX_CHUNK_LINE_LENGTH)
+        .read_until(b'\n', &mut line)?;
+    if !line.ends_with(b"\n") {
+        return Err(Box::new(io::Error::new(
+            ErrorKind::InvalidData,
+            "Chunk line is unterminated or too long",
+        )));
+    }
+    line.pop();
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|e| Box::new(io::Error::new(ErrorKind::InvalidData, e)) as _)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(body: &str, max_size: usize) -> Result<(Vec<u8>, Vec<String>), Box<dyn Error>> {
+        read_chunked_bytes(&mut body.as_bytes(), max_size)
+    }
+
+    fn error_kind(result: Result<(Vec<u8>, Vec<String>), Box<dyn Error>>) -> ErrorKind {
+        let error = result.unwrap_err();
+        error.downcast_ref::<io::Error>().unwrap().kind()
+    }
+
+    #[test]
+    fn reads_chunks() {
+        let (bytes, trailers) = read("5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n", 1024).unwrap();
+        assert_eq!(bytes, b"hello, world");
+        assert!(trailers.is_empty());
+    }
+
+    #[test]
+    fn reads_uppercase_and_padded_sizes() {
+        let (bytes, _) = read("00A\r\n0123456789\r\n0\r\n\r\n", 1024).unwrap();
+        assert_eq!(bytes, b"0123456789");
+    }
+
+    #[test]
+    fn ignores_chunk_extensions() {
+        let body = "5;name=value\r\nhello\r\n0;last\r\n\r\n";
+        let (bytes, _) = read(body, 1024).unwrap();
+        assert_eq!(bytes, b"hello");
+    }
+
+    #[test]
+    fn returns_trailers() {
+        let body = "5\r\nhello\r\n0\r\nX-Checksum: abc\r\nX-Count: 1\r\n\r\n";
+        let (bytes, trailers) = read(body, 1024).unwrap();
+        assert_eq!(bytes, b"hello");
+        assert_eq!(trailers, vec!["X-Checksum: abc", "X-Count: 1"]);
+    }
+
+    #[test]
+    fn leaves_the_next_request_unread() {
+        let mut reader = "5\r\nhello\r\n0\r\n\r\nGET / HTTP/1.1\r\n".as_bytes();
+        read_chunked_bytes(&mut reader, 1024).unwrap();
+        assert_eq!(reader, b"GET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn rejects_bodies_over_the_size_cap() {
+        assert!(read("5\r\nhello\r\n0\r\n\r\n", 5).is_ok());
+        let result = read("5\r\nhello\r\n1\r\n!\r\n0\r\n\r\n", 5);
+        assert_eq!(error_kind(result), ErrorKind::FileTooLarge);
+        // A huge size is refused before reading its data.
+        let result = read("fffffffffffffff\r\n", 5);
+        assert_eq!(error_kind(result), ErrorKind::FileTooLarge);
+    }
+
+    #[test]
+    fn rejects_malformed_sizes() {
+        for body in [
+            "\r\nhello\r\n0\r\n\r\n",
+            "-5\r\nhello\r\n0\r\n\r\n",
+            "+5\r\nhello\r\n0\r\n\r\n",
+            "0x5\r\nhello\r\n0\r\n\r\n",
+            "5 5\r\nhello\r\n0\r\n\r\n",
+            "g\r\nhello\r\n0\r\n\r\n",
+            "fffffffffffffffffffff\r\n",
+        ] {
+            assert_eq!(
+                error_kind(read(body, 1024)),
+                ErrorKind::InvalidData,
+                "{body:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_chunks_without_line_break_after_data() {
+        let result = read("5\r\nhello world\r\n0\r\n\r\n", 1024);
+        assert_eq!(error_kind(result), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_unterminated_and_overlong_size_lines() {
+        assert_eq!(error_kind(read("5", 1024)), ErrorKind::InvalidData);
+        let long_line = format!("5;{}\r\nhello\r\n0\r\n\r\n", "x".repeat(5000));
+        assert_eq!(error_kind(read(&long_line, 1024)), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let result = read("a\r\nhello", 1024);
+        assert_eq!(error_kind(result), ErrorKind::UnexpectedEof);
+    }
+}
@@ -1,11 +1,29 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{self, Receiver};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::utils::general::get_panic_message;
 
 // A thread pool that executes jobs in parallel threads.
 pub struct ThreadPool {
     workers: Vec<Worker>,
     sender: Option<mpsc::Sender<Job>>,
+    queued: QueuedJobs,
+}
+
+// Handle to the number of jobs waiting for a free worker, obtained with ThreadPool::queued_jobs. Can be cloned and read from the jobs.
+#[derive(Debug, Clone, Default)]
+pub struct QueuedJobs {
+    count: Arc<AtomicUsize>,
+}
+
+impl QueuedJobs {
+    pub fn get(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
 }
 
 impl ThreadPool {
@@ -14,26 +32,64 @@ impl ThreadPool {
         let mut workers = Vec::with_capacity(size);
         let (sender, receiver) = mpsc::channel();
         let receiver = Arc::new(Mutex::new(receiver));
+        let queued = QueuedJobs::default();
         for i in 0..size {
-            workers.push(Worker::new(i, Arc::clone(&receiver)));
+            workers.push(Worker::new(i, Arc::clone(&receiver), queued.clone()));
         }
         ThreadPool {
             workers,
             sender: Some(sender),
+            queued,
         }
     }
 
+    // Get a handle to the number of jobs waiting for a free worker, e.g. for a long running job to give up its worker when others are waiting.
+    pub fn queued_jobs(&self) -> QueuedJobs {
+        self.queued.clone()
+    }
+
     pub fn execute<F>(&mut self, f: F)
     where
         F: FnOnce() + Send + 'static,
     {
+        self.queued.count.fetch_add(1, Ordering::SeqCst);
         self.sender.as_ref().unwrap().send(Box::new(f)).unwrap();
     }
+
+    // Stop accepting jobs and wait for the workers to finish the queued and running jobs, at most for the given timeout.
+    // Returns false if some workers were still running when the timeout passed, those workers are left running in the background.
+    pub fn shutdown(&mut self, timeout: Duration) -> bool {
+        drop(self.sender.take());
+        let deadline = Instant::now() + timeout;
+        loop {
+            for worker in &mut self.workers {
+                if worker
+                    .thread
+                    .as_ref()
+                    .is_some_and(|thread| thread.is_finished())
+                {
+                    if let Some(thread) = worker.thread.take() {
+                        let _ = thread.join();
+                    }
+                }
+            }
+            if self.workers.iter().all(|worker| worker.thread.is_none()) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                for worker in &mut self.workers {
+                    worker.thread.take();
+                }
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
 }
 
 impl Drop for ThreadPool {
     fn drop(&mut self) {
-        drop(self.sender.take().unwrap());
+        drop(self.sender.take());
         for worker in &mut self.workers {
             if let Some(thread) = worker.thread.take() {
                 thread.join().unwrap();
@@ -49,17 +105,28 @@ struct Worker {
 }
 
 impl Worker {
-    fn new(_id: usize, receiver: Arc<Mutex<Receiver<Job>>>) -> Worker {
+    fn new(_id: usize, receiver: Arc<Mutex<Receiver<Job>>>, queued: QueuedJobs) -> Worker {
         Worker {
             _id,
             thread: Some(thread::spawn(move || loop {
-                match receiver.lock().unwrap().recv() {
+                // Release the receiver lock before running the job so other workers can pick up jobs.
+                let message = receiver
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .recv();
+                match message {
+                    // A panicking job must not kill the worker, otherwise the pool would shrink with every panic.
                     Ok(job) => {
-                        job();
-                    }
-                    Err(_) => {
-                        break;
+                        queued.count.fetch_sub(1, Ordering::SeqCst);
+                        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                            log!(
+                                "Worker {} Job Panic: {}",
+                                _id,
+                                get_panic_message(payload.as_ref())
+                            );
+                        }
                     }
+                    Err(_) => break,
                 }
             })),
         }
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+// Decode percent-encoded ("%20") characters of a URL component. Optionally decodes "+" as a space, as used in query strings.
+// Invalid escape sequences are kept as is and invalid UTF-8 is replaced.
+pub fn percent_decode(input: &str, plus_as_space: bool) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => match (
+                bytes.get(i + 1).and_then(|b| hex_value(*b)),
+                bytes.get(i + 2).and_then(|b| hex_value(*b)),
+            ) {
+                (Some(high), Some(low)) => {
+                    decoded.push(high << 4 | low);
+                    i += 2;
+                }
+                _ => decoded.push(b'%'),
+            },
+            b'+' if plus_as_space => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+// Parse a query string ("a=1&b=2&a=3") into a map of decoded names to all of their decoded values.
+pub fn parse_query(query: &str) -> HashMap<String, Vec<String>> {
+    let mut params: HashMap<String, Vec<String>> = HashMap::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params
+            .entry(percent_decode(name, true))
+            .or_default()
+            .push(percent_decode(value, true));
+    }
+    params
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_escapes() {
+        assert_eq!(percent_decode("a%20b", false), "a b");
+        assert_eq!(percent_decode("%2F%2f", false), "//");
+        assert_eq!(percent_decode("%C3%A9t%c3%a9", false), "été");
+        assert_eq!(percent_decode("plain/été", false), "plain/été");
+    }
+
+    #[test]
+    fn decodes_only_once() {
+        assert_eq!(percent_decode("%2541", false), "%41");
+    }
+
+    #[test]
+    fn keeps_invalid_escapes() {
+        assert_eq!(percent_decode("%", false), "%");
+        assert_eq!(percent_decode("100%", false), "100%");
+        assert_eq!(percent_decode("%4", false), "%4");
+        assert_eq!(percent_decode("%zz%4g", false), "%zz%4g");
+        assert_eq!(percent_decode("%%41", false), "%A");
+    }
+
+    #[test]
+    fn replaces_invalid_utf8() {
+        assert_eq!(percent_decode("a%FFb", false), "a\u{FFFD}b");
+        assert_eq!(percent_decode("%C3", false), "\u{FFFD}");
+    }
+
+    #[test]
+    fn decodes_plus_as_space_only_when_asked() {
+        assert_eq!(percent_decode("a+b%2B", false), "a+b+");
+        assert_eq!(percent_decode("a+b%2B", true), "a b+");
+    }
+
+    #[test]
+    fn parses_queries() {
+        let query = parse_query("a=1&b=x+y&a=%32&flag&&empty=");
+        assert_eq!(query["a"], vec!["1", "2"]);
+        assert_eq!(query["b"], vec!["x y"]);
+        assert_eq!(query["flag"], vec![""]);
+        assert_eq!(query["empty"], vec![""]);
+        assert_eq!(query.len(), 4);
+    }
+
+    #[test]
+    fn parses_encoded_names_and_separators() {
+        let query = parse_query("a%26b=c%3Dd&e=f=g");
+        assert_eq!(query["a&b"], vec!["c=d"]);
+        assert_eq!(query["e"], vec!["f=g"]);
+    }
+}